@@ -0,0 +1,50 @@
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+fn is_module(name: &str) -> bool {
+	!name.is_empty()
+		&& name
+			.bytes()
+			.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == b'_')
+}
+
+fn main() {
+	let libdir = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join("src/lib");
+	let out = Path::new(&env::var("OUT_DIR").unwrap()).join("stdlib.rs");
+	println!("cargo:rerun-if-changed=src/lib");
+	let mut names = Vec::new();
+	if env::var_os("CARGO_FEATURE_STDLIB").is_some() {
+		if let Ok(dir) = fs::read_dir(&libdir) {
+			for ent in dir.flatten() {
+				if let Ok(name) = ent.file_name().into_string() {
+					if is_module(&name) && ent.path().is_file() {
+						names.push(name);
+					}
+				}
+			}
+		}
+		if names.is_empty() {
+			panic!(
+				"no stdlib modules found in {}\n\
+				 run `git submodule update --init` to fetch the stdlib, \
+				 or build with `--no-default-features` to leave it out",
+				libdir.display()
+			);
+		}
+		names.sort();
+	}
+	let mut f = fs::File::create(&out).unwrap();
+	writeln!(f, "static SOURCES: &[(&str, &str)] = &[").unwrap();
+	for name in names {
+		writeln!(
+			f,
+			"\t({:?}, include_str!({:?})),",
+			name,
+			libdir.join(&name).display().to_string()
+		)
+		.unwrap();
+	}
+	writeln!(f, "];").unwrap();
+}
@@ -0,0 +1,535 @@
+use fxhash::{FxHashMap, FxHashSet};
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::mem;
+use std::path::{Path, PathBuf};
+
+use crate::debug::describe;
+use crate::error::Error;
+use crate::gas;
+use crate::json::{self, Json};
+use crate::loader::{self, Loader};
+use crate::packed;
+use crate::policy::{Input, Output, Policy};
+use crate::tape::{Frame, Tape, ARGS};
+use crate::value::{order, show, Value};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Run {
+	Continue,
+	Next,
+	StepIn,
+	StepOut,
+}
+
+type Lines = Vec<(i64, usize)>;
+
+struct Program {
+	path: PathBuf,
+	lines: Lines,
+	tape: Tape,
+	frames: Vec<Frame>,
+	stop_on_entry: bool,
+	started: bool,
+	halted: bool,
+}
+
+struct Adapter<'a> {
+	out: &'a mut dyn Write,
+	seq: i64,
+	breakpoints: FxHashMap<PathBuf, FxHashSet<Value>>,
+	program: Option<Program>,
+	configured: bool,
+}
+
+fn key(path: &Path) -> PathBuf {
+	fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn source(path: &Path) -> io::Result<(FxHashMap<Value, Value>, Lines)> {
+	if path.extension().is_some_and(|ext| ext == "gas") {
+		let asm = gas::assemble(&fs::read_to_string(path)?);
+		Ok((asm.tape(), asm.addrs.into_iter().zip(asm.lines).collect()))
+	} else if fs::read(path)?.starts_with(packed::MAGIC) {
+		let cells = loader::load(path)?;
+		let mut addrs = cells
+			.keys()
+			.filter_map(|addr| match *addr {
+				Value::I(a) if a >= 0 => Some(a),
+				_ => None,
+			})
+			.collect::<Vec<_>>();
+		addrs.sort_unstable();
+		Ok((cells, addrs.into_iter().zip(1..).collect()))
+	} else {
+		let text = fs::read_to_string(path)?;
+		let skip = text.starts_with(&format!("{}\n", loader::TYPED)) as usize;
+		let lines = (0..)
+			.zip(1 + skip..=text.split_terminator('\n').count())
+			.collect();
+		Ok((loader::parse(&text), lines))
+	}
+}
+
+fn starts(cells: &FxHashMap<Value, Value>, lines: &[(i64, usize)]) -> Vec<Option<i64>> {
+	let mut starts = Vec::with_capacity(lines.len());
+	let (mut start, mut next) = (0, 0);
+	for &(addr, _) in lines {
+		if addr < next {
+			starts.push(Some(start));
+			continue;
+		}
+		let n = match cells.get(&Value::I(addr)) {
+			Some(&Value::I(op)) => gas::operands(op),
+			_ => None,
+		};
+		match n {
+			Some(n) => {
+				start = addr;
+				next = addr.saturating_add(1 + n as i64);
+				starts.push(Some(addr));
+			}
+			None => starts.push(None),
+		}
+	}
+	starts
+}
+
+fn resolve(lines: &[(i64, usize)], starts: &[Option<i64>], line: usize) -> Option<usize> {
+	let at = lines.iter().position(|&(_, l)| l >= line)?;
+	match starts[at] {
+		Some(start) => lines.binary_search_by_key(&start, |&(addr, _)| addr).ok(),
+		None => (at..starts.len()).find(|&n| starts[n] == Some(lines[n].0)),
+	}
+}
+
+fn line(lines: &[(i64, usize)], addr: i64) -> usize {
+	lines
+		.binary_search_by_key(&addr, |&(a, _)| a)
+		.map_or(0, |n| lines[n].1)
+}
+
+impl Program {
+	fn tape(&self, frame: usize) -> Option<&Tape> {
+		match frame {
+			0 => Some(&self.tape),
+			n => self.frames.get(n - 1).map(|f| &f.tape),
+		}
+	}
+
+	fn position(&self, frame: usize) -> Option<&Value> {
+		match self.frames.get(frame) {
+			Some(callee) => Some(&callee.call),
+			None => self.tape(frame).map(|tape| &tape.idx),
+		}
+	}
+
+	fn exec(&mut self, loader: &mut Loader, policy: &mut Policy) -> Result<bool, Error> {
+		match self.frames.split_last_mut() {
+			None => self.tape.exec(loader, policy),
+			Some((frame, rest)) => {
+				let parent = rest.last_mut().map_or(&mut self.tape, |f| &mut f.tape);
+				if !frame.exec(parent, loader, policy)? {
+					self.frames.pop();
+				}
+				Ok(true)
+			}
+		}
+	}
+
+	fn step_in(&mut self, loader: &mut Loader, policy: &mut Policy) -> Result<bool, Error> {
+		let tape = match self.frames.last_mut() {
+			Some(frame) => &mut frame.tape,
+			None => &mut self.tape,
+		};
+		if tape.get(&tape.idx) != Some(&Value::I(14)) {
+			return self.exec(loader, policy);
+		}
+		if let Some(frame) = tape.enter(loader, policy)? {
+			self.frames.push(frame);
+		}
+		Ok(true)
+	}
+
+	fn resume(
+		&mut self,
+		how: Run,
+		breakpoints: &FxHashSet<Value>,
+		loader: &mut Loader,
+		policy: &mut Policy,
+	) -> Result<Option<&'static str>, Error> {
+		let depth = self.frames.len();
+		loop {
+			let running = match how {
+				Run::StepIn => self.step_in(loader, policy)?,
+				_ => self.exec(loader, policy)?,
+			};
+			if !running {
+				self.halted = true;
+				return Ok(None);
+			}
+			match how {
+				Run::Next | Run::StepIn => return Ok(Some("step")),
+				Run::StepOut if self.frames.len() < depth => return Ok(Some("step")),
+				_ if self.frames.is_empty() && breakpoints.contains(&self.tape.idx) => {
+					return Ok(Some("breakpoint"))
+				}
+				_ => (),
+			}
+		}
+	}
+}
+
+impl<'a> Adapter<'a> {
+	fn send(&mut self, mut msg: Vec<(&str, Json)>) -> io::Result<()> {
+		self.seq += 1;
+		msg.insert(0, ("seq", Json::from(self.seq)));
+		json::write(self.out, &Json::obj(msg))
+	}
+
+	fn event(&mut self, event: &str, body: Json) -> io::Result<()> {
+		self.send(vec![
+			("type", Json::from("event")),
+			("event", Json::from(event)),
+			("body", body),
+		])
+	}
+
+	fn respond(&mut self, req: &Json, result: Result<Json, String>) -> io::Result<()> {
+		let mut msg = vec![
+			("type", Json::from("response")),
+			("request_seq", req.get("seq").clone()),
+			("success", Json::from(result.is_ok())),
+			("command", req.get("command").clone()),
+		];
+		match result {
+			Ok(body) => msg.push(("body", body)),
+			Err(e) => msg.push(("message", Json::from(e))),
+		}
+		self.send(msg)
+	}
+
+	fn output(&mut self, category: &str, text: &[u8]) -> io::Result<()> {
+		if text.is_empty() {
+			return Ok(());
+		}
+		self.event(
+			"output",
+			Json::obj(vec![
+				("category", Json::from(category)),
+				(
+					"output",
+					Json::from(String::from_utf8_lossy(text).into_owned()),
+				),
+			]),
+		)
+	}
+
+	fn stopped(&mut self, reason: &str) -> io::Result<()> {
+		self.event(
+			"stopped",
+			Json::obj(vec![
+				("reason", Json::from(reason)),
+				("threadId", Json::from(1)),
+				("allThreadsStopped", Json::from(true)),
+			]),
+		)
+	}
+
+	fn exited(&mut self, status: i64) -> io::Result<()> {
+		self.event("exited", Json::obj(vec![("exitCode", Json::from(status))]))?;
+		self.event("terminated", Json::obj(Vec::new()))
+	}
+
+	fn launch(&mut self, args: &Json, policy: &mut Policy) -> Result<Json, String> {
+		let path = args
+			.get("program")
+			.as_str()
+			.ok_or("launch needs a program")?;
+		let path = Path::new(path);
+		let (cells, lines) = source(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+		let argv = args
+			.get("args")
+			.as_array()
+			.iter()
+			.filter_map(|arg| arg.as_str().map(String::from))
+			.collect::<Vec<_>>();
+		let input = match args.get("input") {
+			Json::Str(s) => s.lines().map(String::from).collect(),
+			input => input
+				.as_array()
+				.iter()
+				.filter_map(|line| line.as_str().map(String::from))
+				.collect(),
+		};
+		policy.input = Input::Buffer(input);
+		policy.output = Output::Capture(Vec::new());
+		let mut tape = Tape::new(path.parent());
+		tape.tape = cells;
+		tape.set_list(ARGS, &argv);
+		self.program = Some(Program {
+			path: key(path),
+			lines,
+			tape,
+			frames: Vec::new(),
+			stop_on_entry: args.get("stopOnEntry").as_bool().unwrap_or(false),
+			started: false,
+			halted: false,
+		});
+		Ok(Json::Null)
+	}
+
+	fn set_breakpoints(&mut self, args: &Json) -> Result<Json, String> {
+		let path = args
+			.get("source")
+			.get("path")
+			.as_str()
+			.ok_or("breakpoints need a source path")?;
+		let path = Path::new(path);
+		let (cells, lines) = source(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+		let starts = starts(&cells, &lines);
+		let mut addrs = FxHashSet::default();
+		let mut out = Vec::new();
+		for bp in args.get("breakpoints").as_array() {
+			let line = bp.get("line").as_i64().unwrap_or(0);
+			out.push(match resolve(&lines, &starts, line.max(0) as usize) {
+				Some(n) => {
+					addrs.insert(Value::I(lines[n].0));
+					Json::obj(vec![
+						("verified", Json::from(true)),
+						("line", Json::from(lines[n].1)),
+					])
+				}
+				None => Json::obj(vec![
+					("verified", Json::from(false)),
+					("line", Json::from(line)),
+					(
+						"message",
+						Json::from("no instruction at or after this line"),
+					),
+				]),
+			});
+		}
+		self.breakpoints.insert(key(path), addrs);
+		Ok(Json::obj(vec![("breakpoints", Json::from(out))]))
+	}
+
+	fn stack_trace(&self) -> Result<Json, String> {
+		let prog = self.program.as_ref().ok_or("no program")?;
+		let mut frames = Vec::new();
+		for (id, frame) in prog.frames.iter().enumerate().rev() {
+			let at = describe(
+				&frame.tape,
+				prog.position(id + 1).unwrap_or(&frame.tape.idx),
+			);
+			frames.push(Json::obj(vec![
+				("id", Json::from(id + 1)),
+				("name", Json::from(format!("{} at {}", frame.name, at))),
+				("line", Json::from(0)),
+				("column", Json::from(0)),
+			]));
+		}
+		let idx = prog.position(0).unwrap_or(&prog.tape.idx);
+		let line = match *idx {
+			Value::I(a) => line(&prog.lines, a),
+			_ => 0,
+		};
+		let name = prog
+			.path
+			.file_name()
+			.map_or_else(String::new, |n| n.to_string_lossy().into_owned());
+		frames.push(Json::obj(vec![
+			("id", Json::from(0)),
+			("name", Json::from(describe(&prog.tape, idx))),
+			(
+				"source",
+				Json::obj(vec![
+					("name", Json::from(name)),
+					("path", Json::from(prog.path.to_string_lossy().into_owned())),
+				]),
+			),
+			("line", Json::from(line)),
+			("column", Json::from(1)),
+		]));
+		let total = frames.len();
+		Ok(Json::obj(vec![
+			("stackFrames", Json::from(frames)),
+			("totalFrames", Json::from(total)),
+		]))
+	}
+
+	fn scopes(&self, args: &Json) -> Result<Json, String> {
+		let id = args.get("frameId").as_i64().unwrap_or(0);
+		let scope = |name: &str, reference: i64| {
+			Json::obj(vec![
+				("name", Json::from(name)),
+				("variablesReference", Json::from(reference)),
+				("expensive", Json::from(false)),
+			])
+		};
+		Ok(Json::obj(vec![(
+			"scopes",
+			Json::from(vec![
+				scope("Registers", id * 2 + 1),
+				scope("Cells", id * 2 + 2),
+			]),
+		)]))
+	}
+
+	fn variables(&self, args: &Json) -> Result<Json, String> {
+		let prog = self.program.as_ref().ok_or("no program")?;
+		let reference = args.get("variablesReference").as_i64().unwrap_or(0) - 1;
+		let tape = prog
+			.tape(reference.max(0) as usize / 2)
+			.ok_or("no such frame")?;
+		let var = |name: String, value: String| {
+			Json::obj(vec![
+				("name", Json::from(name)),
+				("value", Json::from(value)),
+				("variablesReference", Json::from(0)),
+			])
+		};
+		let vars = if reference % 2 == 0 {
+			vec![
+				var(String::from("idx"), show(&tape.idx)),
+				var(String::from("dir"), tape.dir.to_string()),
+			]
+		} else {
+			let mut addrs = tape
+				.tape
+				.keys()
+				.chain(tape.image.keys())
+				.collect::<FxHashSet<_>>()
+				.into_iter()
+				.collect::<Vec<_>>();
+			addrs.sort_by_key(|addr| order(addr));
+			addrs
+				.into_iter()
+				.filter_map(|addr| tape.get(addr).map(|val| var(show(addr), show(val))))
+				.collect()
+		};
+		Ok(Json::obj(vec![("variables", Json::from(vars))]))
+	}
+
+	fn run(&mut self, how: Run, loader: &mut Loader, policy: &mut Policy) -> io::Result<()> {
+		let prog = match self.program {
+			Some(ref mut prog) => prog,
+			None => return Ok(()),
+		};
+		let none = FxHashSet::default();
+		let breakpoints = self.breakpoints.get(&prog.path).unwrap_or(&none);
+		let result = prog.resume(how, breakpoints, loader, policy);
+		let status = prog.tape.status(policy);
+		if let Output::Capture(ref mut buf) = policy.output {
+			self.output("stdout", &mem::take(buf))?;
+		}
+		match result {
+			Ok(Some(reason)) => self.stopped(reason),
+			Ok(None) => self.exited(status),
+			Err(e) => {
+				if let Some(ref mut prog) = self.program {
+					prog.halted = true;
+				}
+				self.output("stderr", format!("{}\n", e).as_bytes())?;
+				self.exited(1)
+			}
+		}
+	}
+
+	fn start(&mut self, loader: &mut Loader, policy: &mut Policy) -> io::Result<()> {
+		let prog = match self.program {
+			Some(ref mut prog) if self.configured && !prog.started => prog,
+			_ => return Ok(()),
+		};
+		prog.started = true;
+		let entry = self
+			.breakpoints
+			.get(&prog.path)
+			.is_some_and(|b| b.contains(&prog.tape.idx));
+		if prog.stop_on_entry {
+			self.stopped("entry")
+		} else if entry {
+			self.stopped("breakpoint")
+		} else {
+			self.run(Run::Continue, loader, policy)
+		}
+	}
+
+	fn handle(&mut self, req: &Json, loader: &mut Loader, policy: &mut Policy) -> io::Result<bool> {
+		let args = req.get("arguments");
+		let cmd = req.get("command").as_str().unwrap_or("");
+		let how = match cmd {
+			"continue" => Some(Run::Continue),
+			"next" => Some(Run::Next),
+			"stepIn" => Some(Run::StepIn),
+			"stepOut" => Some(Run::StepOut),
+			_ => None,
+		};
+		let result = match cmd {
+			"initialize" => Ok(Json::obj(vec![(
+				"supportsConfigurationDoneRequest",
+				Json::from(true),
+			)])),
+			"launch" => self.launch(args, policy),
+			"setBreakpoints" => self.set_breakpoints(args),
+			"setExceptionBreakpoints" => Ok(Json::obj(Vec::new())),
+			"configurationDone" => {
+				self.configured = true;
+				Ok(Json::Null)
+			}
+			"threads" => Ok(Json::obj(vec![(
+				"threads",
+				Json::from(vec![Json::obj(vec![
+					("id", Json::from(1)),
+					("name", Json::from("main")),
+				])]),
+			)])),
+			"stackTrace" => self.stack_trace(),
+			"scopes" => self.scopes(args),
+			"variables" => self.variables(args),
+			_ if how.is_some() => match self.program {
+				Some(ref prog) if prog.started && !prog.halted => {
+					Ok(Json::obj(vec![("allThreadsContinued", Json::from(true))]))
+				}
+				Some(ref prog) if prog.halted => Err(String::from("the program has ended")),
+				_ => Err(String::from("the program is not running")),
+			},
+			"disconnect" | "terminate" => Ok(Json::Null),
+			_ => Err(format!("unsupported request: {}", cmd)),
+		};
+		let ok = result.is_ok();
+		self.respond(req, result)?;
+		match cmd {
+			"initialize" => self.event("initialized", Json::obj(Vec::new()))?,
+			"launch" | "configurationDone" if ok => self.start(loader, policy)?,
+			"terminate" => self.event("terminated", Json::obj(Vec::new()))?,
+			"disconnect" => return Ok(false),
+			_ => (),
+		}
+		if let (Some(how), true) = (how, ok) {
+			self.run(how, loader, policy)?;
+		}
+		Ok(true)
+	}
+}
+
+pub fn serve(
+	input: &mut dyn BufRead,
+	output: &mut dyn Write,
+	loader: &mut Loader,
+	policy: &mut Policy,
+) -> io::Result<()> {
+	let mut adapter = Adapter {
+		out: output,
+		seq: 0,
+		breakpoints: FxHashMap::default(),
+		program: None,
+		configured: false,
+	};
+	while let Some(req) = json::read(input)? {
+		if req.get("type").as_str() == Some("request") && !adapter.handle(&req, loader, policy)? {
+			break;
+		}
+	}
+	Ok(())
+}
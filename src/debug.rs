@@ -0,0 +1,346 @@
+use fxhash::FxHashSet;
+use std::collections::VecDeque;
+use std::io::{self, BufRead, Write};
+
+use crate::error::opname;
+use crate::gas;
+use crate::loader::Loader;
+use crate::policy::Policy;
+use crate::tape::{Tape, Watch};
+use crate::value::{show, Value};
+
+const HELP: &str = "\
+  step [n]              run n instructions, 1 by default
+  continue              run until a breakpoint or the program ends
+  break addr            stop before running the instruction at addr
+  delete addr           remove a breakpoint
+  watch addr [to]       stop after an instruction writes addr or the range to
+  trace addr [to]       report writes to addr or the range to without stopping
+  unwatch addr          remove watch and trace points starting at addr
+  back [n]              undo n instructions, 1 by default
+  back write addr       undo up to and including the last write to addr
+  print addr [to]       show a cell or a range of cells
+  where                 show the current instruction, idx and dir
+  help                  show this help
+  quit                  leave the debugger
+commands may be abbreviated to a prefix, b is break, an empty line repeats the last one,
+stepping forward after back replays the undone steps without repeating their input
+or output";
+
+const HISTORY: usize = 1_000_000;
+
+pub struct Step {
+	pub idx: Value,
+	pub dir: bool,
+	pub next_idx: Value,
+	pub next_dir: bool,
+	pub cells: Vec<(Value, Option<Value>, Option<Value>)>,
+	pub halted: bool,
+}
+
+pub fn describe(tape: &Tape, idx: &Value) -> String {
+	let mut idx = idx.clone();
+	let mut s = format!("{}: ", show(&idx));
+	match tape.get(&idx) {
+		Some(&Value::I(op)) if gas::operands(op).is_some() => {
+			s.push_str(opname(op));
+			for _ in 0..gas::operands(op).unwrap_or(0) {
+				idx.advance(tape.dir);
+				s.push(' ');
+				s.push_str(&show(&tape.read_val(&idx)));
+			}
+		}
+		Some(val) => s.push_str(&show(val)),
+		None => s.push_str("end of program"),
+	}
+	s
+}
+
+pub struct Debugger {
+	pub tape: Tape,
+	pub breakpoints: FxHashSet<Value>,
+	pub halted: bool,
+	pub history: VecDeque<Step>,
+	pub redo: Vec<Step>,
+}
+
+impl Debugger {
+	pub fn new(tape: Tape) -> Debugger {
+		Debugger {
+			tape,
+			breakpoints: FxHashSet::default(),
+			halted: false,
+			history: VecDeque::new(),
+			redo: Vec::new(),
+		}
+	}
+
+	pub fn current(&self) -> String {
+		describe(&self.tape, &self.tape.idx)
+	}
+
+	fn stopped(&self, policy: &Policy) -> String {
+		match self.tape.status(policy) {
+			status if self.halted && status != 0 => {
+				format!("{}\nexited with status {}", self.current(), status)
+			}
+			_ => self.current(),
+		}
+	}
+
+	fn exec(&mut self, loader: &mut Loader, policy: &mut Policy) -> Result<(), String> {
+		if let Some(step) = self.redo.pop() {
+			for (addr, _, new) in &step.cells {
+				match new {
+					Some(val) => self.tape.tape.insert(addr.clone(), val.clone()),
+					None => self.tape.tape.remove(addr),
+				};
+			}
+			for (addr, old, _) in &step.cells {
+				self.tape.hit(&step.idx, addr, old);
+			}
+			self.tape.idx = step.next_idx.clone();
+			self.tape.dir = step.next_dir;
+			self.halted = step.halted;
+			self.history.push_back(step);
+			return Ok(());
+		}
+		let (idx, dir) = (self.tape.idx.clone(), self.tape.dir);
+		self.tape.journal = Some(Vec::new());
+		let result = self.tape.exec(loader, policy);
+		let cells = self
+			.tape
+			.journal
+			.take()
+			.unwrap_or_default()
+			.into_iter()
+			.map(|(addr, old)| {
+				let new = self.tape.tape.get(&addr).cloned();
+				(addr, old, new)
+			})
+			.collect();
+		self.halted = !matches!(result, Ok(true));
+		if self.history.len() >= HISTORY {
+			self.history.pop_front();
+		}
+		self.history.push_back(Step {
+			idx,
+			dir,
+			next_idx: self.tape.idx.clone(),
+			next_dir: self.tape.dir,
+			cells,
+			halted: self.halted,
+		});
+		result.map(|_| ()).map_err(|e| e.to_string())
+	}
+
+	pub fn step(&mut self, n: u64, loader: &mut Loader, policy: &mut Policy) -> Result<(), String> {
+		for _ in 0..n {
+			if self.halted {
+				return Err(String::from("the program has ended"));
+			}
+			self.exec(loader, policy)?;
+		}
+		Ok(())
+	}
+
+	pub fn back(&mut self) -> bool {
+		let step = match self.history.pop_back() {
+			Some(step) => step,
+			None => return false,
+		};
+		for (addr, old, _) in step.cells.iter().rev() {
+			match old {
+				Some(val) => self.tape.tape.insert(addr.clone(), val.clone()),
+				None => self.tape.tape.remove(addr),
+			};
+		}
+		self.tape.idx = step.idx.clone();
+		self.tape.dir = step.dir;
+		self.halted = false;
+		self.redo.push(step);
+		true
+	}
+
+	pub fn back_to_write(&mut self, addr: &Value) -> bool {
+		let wrote = |step: &Step| step.cells.iter().any(|(a, _, _)| a == addr);
+		if !self.history.iter().any(wrote) {
+			return false;
+		}
+		while let Some(step) = self.history.back() {
+			let done = wrote(step);
+			self.back();
+			if done {
+				break;
+			}
+		}
+		true
+	}
+
+	pub fn resume(&mut self, loader: &mut Loader, policy: &mut Policy) -> Result<(), String> {
+		loop {
+			let seen = self.tape.hits.len();
+			self.step(1, loader, policy)?;
+			if self.halted
+				|| self.breakpoints.contains(&self.tape.idx)
+				|| self.tape.hits[seen..].iter().any(|hit| hit.stop)
+			{
+				return Ok(());
+			}
+		}
+	}
+
+	fn report(&mut self, seen: usize, policy: &Policy) -> String {
+		let mut out = self.tape.hits[seen..]
+			.iter()
+			.map(|hit| hit.to_string())
+			.collect::<Vec<_>>();
+		out.push(self.stopped(policy));
+		out.join("\n")
+	}
+
+	fn watch(&mut self, from: &str, to: Option<&str>, stop: bool) -> String {
+		let from = Value::from(from);
+		let to = to.map_or_else(|| from.clone(), Value::from);
+		let what = if stop { "watching" } else { "tracing" };
+		let s = if to == from {
+			format!("{} {}", what, from)
+		} else {
+			format!("{} {} to {}", what, from, to)
+		};
+		self.tape.watches.push(Watch { from, to, stop });
+		s
+	}
+
+	pub fn command(
+		&mut self,
+		line: &str,
+		loader: &mut Loader,
+		policy: &mut Policy,
+	) -> Result<String, String> {
+		let words = line.split_whitespace().collect::<Vec<_>>();
+		match &words[..] {
+			[cmd, rest @ ..] if "step".starts_with(cmd) && rest.len() <= 1 => {
+				let n = match rest.first() {
+					Some(n) => n.parse().map_err(|_| format!("not a number: {}", n))?,
+					None => 1,
+				};
+				let seen = self.tape.hits.len();
+				self.step(n, loader, policy)?;
+				Ok(self.report(seen, policy))
+			}
+			[cmd, what, addr] if "back".starts_with(cmd) && cmd.len() > 1 && *what == "write" => {
+				if !self.back_to_write(&Value::from(*addr)) {
+					return Err(format!("no recorded write to {}", addr));
+				}
+				Ok(self.current())
+			}
+			[cmd, rest @ ..] if "back".starts_with(cmd) && cmd.len() > 1 && rest.len() <= 1 => {
+				let n = match rest.first() {
+					Some(n) => n.parse().map_err(|_| format!("not a number: {}", n))?,
+					None => 1,
+				};
+				for _ in 0..n {
+					if !self.back() {
+						return Err(format!(
+							"at the start of the recorded history\n{}",
+							self.current()
+						));
+					}
+				}
+				Ok(self.current())
+			}
+			[cmd] if "continue".starts_with(cmd) => {
+				let seen = self.tape.hits.len();
+				self.resume(loader, policy)?;
+				Ok(self.report(seen, policy))
+			}
+			[cmd, addr] if "break".starts_with(cmd) => {
+				self.breakpoints.insert(Value::from(*addr));
+				Ok(format!("breakpoint at {}", addr))
+			}
+			[cmd, from, rest @ ..] if "watch".starts_with(cmd) && rest.len() <= 1 => {
+				Ok(self.watch(from, rest.first().copied(), true))
+			}
+			[cmd, from, rest @ ..] if "trace".starts_with(cmd) && rest.len() <= 1 => {
+				Ok(self.watch(from, rest.first().copied(), false))
+			}
+			[cmd, addr] if "unwatch".starts_with(cmd) => {
+				let addr = Value::from(*addr);
+				let len = self.tape.watches.len();
+				self.tape.watches.retain(|w| w.from != addr);
+				if self.tape.watches.len() == len {
+					return Err(format!("no watchpoint at {}", addr));
+				}
+				Ok(String::new())
+			}
+			[cmd, addr] if "delete".starts_with(cmd) => {
+				if self.breakpoints.remove(&Value::from(*addr)) {
+					Ok(String::new())
+				} else {
+					Err(format!("no breakpoint at {}", addr))
+				}
+			}
+			[cmd, addr] if "print".starts_with(cmd) => {
+				let addr = Value::from(*addr);
+				Ok(format!(
+					"{}: {}",
+					show(&addr),
+					show(&self.tape.read_val(&addr))
+				))
+			}
+			[cmd, from, to] if "print".starts_with(cmd) => {
+				let (from, to) = match (Value::from(*from), Value::from(*to)) {
+					(Value::I(from), Value::I(to)) => (from, to),
+					_ => return Err(String::from("a range needs integer addresses")),
+				};
+				Ok((from..=to)
+					.map(|a| format!("{}: {}", a, show(&self.tape.read_val(&Value::I(a)))))
+					.collect::<Vec<_>>()
+					.join("\n"))
+			}
+			[cmd] if "where".starts_with(cmd) => Ok(format!(
+				"{}\nidx: {} dir: {}",
+				self.current(),
+				show(&self.tape.idx),
+				self.tape.dir
+			)),
+			[cmd] if "help".starts_with(cmd) => Ok(String::from(HELP)),
+			_ => Err(format!("unknown command: {}", line)),
+		}
+	}
+}
+
+pub fn debug(tape: Tape, loader: &mut Loader, policy: &mut Policy) {
+	let mut dbg = Debugger::new(tape);
+	let stdin = io::stdin();
+	let mut line = String::new();
+	let mut last = String::new();
+	println!("{}", dbg.current());
+	loop {
+		print!("(oil) ");
+		io::stdout().flush().ok();
+		line.clear();
+		if stdin.lock().read_line(&mut line).unwrap_or(0) == 0 {
+			println!();
+			return;
+		}
+		let cmd = line.trim();
+		if !cmd.is_empty() {
+			last = String::from(cmd);
+		}
+		if last.is_empty() {
+			continue;
+		}
+		if "quit".starts_with(&last[..]) {
+			return;
+		}
+		let result = dbg.command(&last, loader, policy);
+		policy.flush().ok();
+		match result {
+			Ok(ref out) if out.is_empty() => (),
+			Ok(out) => println!("{}", out),
+			Err(e) => println!("error: {}", e),
+		}
+	}
+}
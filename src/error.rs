@@ -0,0 +1,91 @@
+use std::fmt::{self, Display};
+use std::io;
+use std::path::PathBuf;
+
+use crate::tape::Hit;
+use crate::value::Value;
+
+pub enum Error {
+	PathTraversal(String),
+	AbsolutePath(String),
+	OutsideSandbox(PathBuf),
+	FileModulesDisabled(String),
+	BadModule(PathBuf, io::Error),
+	Denied(i64, Value),
+	ModuleDenied(String, Value),
+	OutputLimit(usize, i64, Value),
+	StepLimit(u64, Value),
+	Write(io::Error),
+	Watch(Hit),
+}
+
+pub fn opname(op: i64) -> &'static str {
+	match op {
+		0 => "nop",
+		1 => "copy",
+		2 => "reverse",
+		3 => "quit",
+		4 => "output",
+		5 => "read",
+		6 => "jump",
+		7 => "relative_jump",
+		8 => "increment",
+		9 => "decrement",
+		10 => "conditional_jump",
+		11 => "newline",
+		12 => "explode",
+		13 => "implode",
+		14 => "call",
+		15 => "rand",
+		16 => "ord",
+		17 => "chr",
+		_ => "nop",
+	}
+}
+
+impl Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Error::PathTraversal(ref name) => {
+				write!(f, "call {}: path traverses out of its root", name)
+			}
+			Error::AbsolutePath(ref name) => {
+				write!(f, "call {}: absolute module paths are not allowed", name)
+			}
+			Error::OutsideSandbox(ref path) => {
+				write!(
+					f,
+					"call {}: module resolves outside the sandbox",
+					path.display()
+				)
+			}
+			Error::FileModulesDisabled(ref name) => {
+				write!(f, "call {}: file modules are disabled", name)
+			}
+			Error::BadModule(ref path, ref e) => write!(f, "call {}: {}", path.display(), e),
+			Error::Denied(op, ref addr) => {
+				write!(f, "{} (opcode {}) denied at {}", opname(op), op, addr)
+			}
+			Error::ModuleDenied(ref name, ref addr) => {
+				write!(
+					f,
+					"call (opcode 14) denied at {}: module {} is not allowed",
+					addr, name
+				)
+			}
+			Error::OutputLimit(limit, op, ref addr) => write!(
+				f,
+				"{} (opcode {}) exceeded the output limit of {} bytes at {}",
+				opname(op),
+				op,
+				limit,
+				addr
+			),
+			Error::StepLimit(limit, ref addr) => {
+				write!(f, "step limit of {} exceeded at {}", limit, addr)
+			}
+			Error::Write(ref e) => write!(f, "writing output: {}", e),
+			Error::Watch(ref hit) => write!(f, "watchpoint: {}", hit),
+		}
+	}
+}
@@ -0,0 +1,405 @@
+use fxhash::FxHashMap;
+use std::rc::Rc;
+
+use crate::error::opname;
+use crate::value::{quote, unquote, Value};
+
+pub const MNEMONICS: &[(&str, &str)] = &[
+	("nop", "0"),
+	("copy", "1"),
+	("mov", "1"),
+	("reverse", "2"),
+	("quit", "3"),
+	("exit", "3"),
+	("return", "3"),
+	("output", "4"),
+	("write", "4"),
+	("user_input", "5"),
+	("read", "5"),
+	("jump", "6"),
+	("jmp", "6"),
+	("relative_jump", "7"),
+	("jr", "7"),
+	("increment", "8"),
+	("+", "8"),
+	("decrement", "9"),
+	("-", "9"),
+	("conditional_jump", "10"),
+	("je", "10"),
+	("newline", "11"),
+	("explode", "12"),
+	("implode", "13"),
+	("call", "14"),
+	("rand", "15"),
+	("ord", "16"),
+	("chr", "17"),
+];
+
+pub fn mnemonic(word: &str) -> Option<&'static str> {
+	MNEMONICS
+		.iter()
+		.find(|&&(name, _)| name == word)
+		.map(|&(_, op)| op)
+}
+
+pub struct TestBlock {
+	pub name: String,
+	pub input: Vec<String>,
+	pub output: Vec<String>,
+	pub cells: Vec<(String, String)>,
+}
+
+pub struct Assembly {
+	pub cells: Vec<Value>,
+	pub addrs: Vec<i64>,
+	pub lines: Vec<usize>,
+	pub labels: Vec<(String, i64)>,
+	pub tests: Vec<TestBlock>,
+	pub warnings: Vec<String>,
+}
+
+fn directive(line: &str) -> Option<(&str, &str)> {
+	let (word, arg) = match line.find(' ') {
+		Some(sp) => (&line[..sp], &line[sp + 1..]),
+		None => (line, ""),
+	};
+	match word {
+		".org" | ".test" | ".in" | ".out" | ".expect_cell" | ".end" => Some((word, arg)),
+		_ => None,
+	}
+}
+
+pub enum Line<'a> {
+	Directive(&'a str, &'a str),
+	Op(&'static str),
+	Ref(&'a str),
+	Label(&'a str),
+	Str(&'a str),
+	Typed(Value),
+	Heredoc(Value),
+	Body(&'a str),
+	Comment,
+	Literal(&'a str),
+}
+
+pub const TYPED: &str = "#gas typed";
+
+pub fn parse(line: &str, typed: bool) -> Line<'_> {
+	let word = line.trim_start_matches(&[' ', '\t'][..]);
+	if let Some((word, arg)) = directive(word) {
+		Line::Directive(word, arg)
+	} else if let Some(op) = mnemonic(word) {
+		Line::Op(op)
+	} else if let Some(label) = word.strip_prefix('$') {
+		Line::Ref(label)
+	} else if let Some(label) = word.strip_prefix(':') {
+		Line::Label(label)
+	} else if let Some(val) = unquote(word).filter(|_| typed) {
+		Line::Typed(val)
+	} else if let Some(s) = line.strip_prefix('"') {
+		Line::Str(s)
+	} else if line.starts_with('#') {
+		Line::Comment
+	} else {
+		Line::Literal(line)
+	}
+}
+
+const HEREDOC: &str = "\"\"\"";
+
+fn fence(line: &str) -> bool {
+	line.trim_matches(&[' ', '\t'][..]) == HEREDOC
+}
+
+pub fn lines(src: &str) -> Vec<Line<'_>> {
+	let mut out = Vec::new();
+	let mut typed = false;
+	let mut text = src.lines();
+	while let Some(line) = text.next() {
+		typed |= line.trim() == TYPED;
+		if !typed || !fence(line) {
+			out.push(parse(line, typed));
+			continue;
+		}
+		let at = out.len();
+		out.push(Line::Comment);
+		let mut body = Vec::new();
+		for line in text.by_ref() {
+			out.push(Line::Body(line));
+			if fence(line) {
+				break;
+			}
+			body.push(line);
+		}
+		out[at] = Line::Heredoc(Value::S(Rc::new(body.join("\n"))));
+	}
+	out
+}
+
+pub fn assemble(src: &str) -> Assembly {
+	let mut cells = Vec::new();
+	let mut addrs = Vec::new();
+	let mut addr = 0i64;
+	let mut map = Vec::new();
+	let mut labels = FxHashMap::default();
+	let mut labelfill = Vec::new();
+	let mut tests = Vec::new();
+	let mut block: Option<TestBlock> = None;
+	let mut warnings = Vec::new();
+	let mut open = false;
+	for (n, line) in lines(src).into_iter().enumerate() {
+		let idx = cells.len();
+		cells.push(match line {
+			Line::Directive(word, arg) => {
+				match (word, block.as_mut()) {
+					(".org", _) => match arg.trim().parse() {
+						Ok(to) if to >= addr => addr = to,
+						_ => warnings
+							.push(format!(".org {} is not an address from {} on", arg, addr)),
+					},
+					(".test", _) => {
+						tests.extend(block.take());
+						block = Some(TestBlock {
+							name: String::from(arg),
+							input: Vec::new(),
+							output: Vec::new(),
+							cells: Vec::new(),
+						});
+					}
+					(".in", Some(b)) => b.input.push(String::from(arg)),
+					(".out", Some(b)) => b.output.push(String::from(arg)),
+					(".expect_cell", Some(b)) => {
+						let (addr, val) = arg.split_at(arg.find(' ').unwrap_or(arg.len()));
+						b.cells.push((
+							String::from(addr),
+							String::from(val.strip_prefix(' ').unwrap_or(val)),
+						));
+					}
+					(".end", Some(_)) => tests.extend(block.take()),
+					_ => warnings.push(format!("{} outside of .test block", word)),
+				}
+				continue;
+			}
+			Line::Op(op) => Value::from(op),
+			Line::Ref(label) => {
+				labelfill.push((idx, label));
+				Value::from(format!("${}", label))
+			}
+			Line::Label(label) => {
+				if let Some(oldaddr) = labels.insert(label, addr) {
+					warnings.push(format!("Duplicate labels: {} {}", oldaddr, addr));
+				}
+				continue;
+			}
+			Line::Typed(val) => val,
+			Line::Heredoc(val) => {
+				open = true;
+				val
+			}
+			Line::Body(text) => {
+				open = !fence(text);
+				continue;
+			}
+			Line::Str(s) | Line::Literal(s) => Value::from(s),
+			Line::Comment => continue,
+		});
+		addrs.push(addr);
+		addr = addr.saturating_add(1);
+		map.push(n + 1);
+	}
+	if open {
+		warnings.push(String::from("heredoc is missing closing \"\"\""));
+	}
+	for (idx, label) in labelfill {
+		match labels.get(label) {
+			Some(&addr) => cells[idx] = Value::I(addr),
+			None => warnings.push(format!("Unknown label: {}", label)),
+		}
+	}
+	if let Some(b) = block {
+		warnings.push(format!(".test {} is missing .end", b.name));
+		tests.push(b);
+	}
+	for b in tests.iter_mut() {
+		for (addr, _) in b.cells.iter_mut() {
+			if let Some(label) = addr.strip_prefix('$') {
+				if let Some(at) = labels.get(label) {
+					*addr = at.to_string();
+				} else {
+					warnings.push(format!("Unknown label: {}", label));
+				}
+			}
+		}
+	}
+	let mut labels = labels
+		.into_iter()
+		.map(|(name, addr)| (String::from(name), addr))
+		.collect::<Vec<_>>();
+	labels.sort_by(|a, b| (a.1, &a.0).cmp(&(b.1, &b.0)));
+	Assembly {
+		cells,
+		addrs,
+		lines: map,
+		labels,
+		tests,
+		warnings,
+	}
+}
+
+impl Assembly {
+	pub fn tape(&self) -> FxHashMap<Value, Value> {
+		self.addrs
+			.iter()
+			.zip(&self.cells)
+			.map(|(&addr, cell)| (Value::I(addr), cell.clone()))
+			.collect()
+	}
+
+	pub fn dense(&self) -> bool {
+		self.addrs
+			.iter()
+			.enumerate()
+			.all(|(idx, &addr)| addr == idx as i64)
+	}
+}
+
+pub fn operands(op: i64) -> Option<usize> {
+	match op {
+		0 | 2 | 3 | 11 => Some(0),
+		4..=9 | 15 => Some(1),
+		1 | 12 | 16 => Some(2),
+		13 | 14 | 17 => Some(3),
+		10 => Some(4),
+		_ => None,
+	}
+}
+
+fn legacy(val: &Value) -> bool {
+	let s = val.to_string();
+	Value::from(&s[..]) == *val && !s.contains(&['\n', '\r'][..])
+}
+
+fn literal(val: &Value, typed: bool) -> String {
+	let s = val.to_string();
+	if Value::from(&s[..]) == *val
+		&& !s.contains(char::is_control)
+		&& mnemonic(&s).is_none()
+		&& directive(&s).is_none()
+		&& !s.starts_with(&['"', '\'', '$', ':', '#', ' ', '\t'][..])
+	{
+		s
+	} else if typed {
+		quote(val)
+	} else {
+		format!("\"{}", s)
+	}
+}
+
+pub fn disassemble(
+	cells: &FxHashMap<Value, Value>,
+	symbols: &[(String, i64)],
+) -> (Vec<String>, Vec<String>) {
+	let mut warnings = Vec::new();
+	let mut addrs = Vec::with_capacity(cells.len() + symbols.len());
+	for addr in cells.keys() {
+		match *addr {
+			Value::I(a) if a >= 0 => addrs.push(a),
+			_ => warnings.push(format!("cell {} is outside the gas address space", addr)),
+		}
+	}
+	warnings.sort();
+	let mut symbols = symbols
+		.iter()
+		.filter(|&&(_, addr)| addr >= 0)
+		.collect::<Vec<_>>();
+	symbols.sort_by(|a, b| (a.1, &a.0).cmp(&(b.1, &b.0)));
+	addrs.extend(symbols.iter().map(|&&(_, addr)| addr));
+	addrs.sort_unstable();
+	addrs.dedup();
+	let typed = !cells.values().all(legacy);
+	let mut lines = Vec::with_capacity(addrs.len() + symbols.len() + 1);
+	if typed {
+		lines.push(String::from(TYPED));
+	}
+	let mut symbols = symbols.into_iter().peekable();
+	let (mut next, mut operand) = (0, 0);
+	for addr in addrs {
+		if addr != next {
+			lines.push(format!(".org {}", addr));
+		}
+		while let Some((name, _)) = symbols.next_if(|&&(_, at)| at == addr) {
+			lines.push(format!(":{}", name));
+		}
+		next = addr.saturating_add(1);
+		let cell = match cells.get(&Value::I(addr)) {
+			Some(cell) => cell,
+			None => continue,
+		};
+		match *cell {
+			Value::I(op) if addr >= operand => match operands(op) {
+				Some(n) => {
+					lines.push(String::from(opname(op)));
+					operand = next.saturating_add(n as i64);
+				}
+				None => lines.push(literal(cell, typed)),
+			},
+			_ => lines.push(literal(cell, typed)),
+		}
+	}
+	(lines, warnings)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Spelling {
+	Canonical,
+	Short,
+}
+
+pub fn spell(op: &str, spelling: Spelling) -> &'static str {
+	let code = op.parse().unwrap_or(0);
+	match spelling {
+		Spelling::Canonical => opname(code),
+		Spelling::Short => MNEMONICS
+			.iter()
+			.filter(|&&(_, o)| o == op)
+			.min_by_key(|&&(name, _)| name.len())
+			.map_or(opname(code), |&(name, _)| name),
+	}
+}
+
+pub fn format(src: &str, spelling: Spelling) -> String {
+	let lines = lines(src);
+	let mut indent = vec![false; lines.len()];
+	let mut pending = 0;
+	for (n, line) in lines.iter().enumerate() {
+		match *line {
+			Line::Op(op) if pending == 0 => {
+				pending = operands(op.parse().unwrap_or(0)).unwrap_or(0)
+			}
+			Line::Op(_) | Line::Ref(_) | Line::Typed(_) | Line::Heredoc(_) if pending > 0 => {
+				indent[n] = true;
+				pending -= 1;
+			}
+			Line::Str(_) | Line::Literal(_) if pending > 0 => pending -= 1,
+			_ => (),
+		}
+	}
+	let mut out = String::with_capacity(src.len());
+	for (n, (text, line)) in src.lines().zip(&lines).enumerate() {
+		if let Line::Body(_) | Line::Str(_) | Line::Literal(_) | Line::Comment = *line {
+			out.push_str(text);
+			out.push('\n');
+			continue;
+		}
+		if indent[n] {
+			out.push('\t');
+		}
+		let text = text.trim_start_matches(&[' ', '\t'][..]);
+		match *line {
+			Line::Op(op) => out.push_str(spell(op, spelling)),
+			_ => out.push_str(text),
+		}
+		out.push('\n');
+	}
+	out
+}
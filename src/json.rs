@@ -0,0 +1,352 @@
+use std::fmt::{self, Write as _};
+use std::io::{self, BufRead, Write};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+	Null,
+	Bool(bool),
+	Num(f64),
+	Str(String),
+	Arr(Vec<Json>),
+	Obj(Vec<(String, Json)>),
+}
+
+impl From<bool> for Json {
+	fn from(b: bool) -> Json {
+		Json::Bool(b)
+	}
+}
+
+impl From<i32> for Json {
+	fn from(n: i32) -> Json {
+		Json::Num(n as f64)
+	}
+}
+
+impl From<i64> for Json {
+	fn from(n: i64) -> Json {
+		Json::Num(n as f64)
+	}
+}
+
+impl From<usize> for Json {
+	fn from(n: usize) -> Json {
+		Json::Num(n as f64)
+	}
+}
+
+impl From<&str> for Json {
+	fn from(s: &str) -> Json {
+		Json::Str(String::from(s))
+	}
+}
+
+impl From<String> for Json {
+	fn from(s: String) -> Json {
+		Json::Str(s)
+	}
+}
+
+impl From<Vec<Json>> for Json {
+	fn from(v: Vec<Json>) -> Json {
+		Json::Arr(v)
+	}
+}
+
+impl Json {
+	pub fn obj(fields: Vec<(&str, Json)>) -> Json {
+		Json::Obj(
+			fields
+				.into_iter()
+				.map(|(k, v)| (String::from(k), v))
+				.collect(),
+		)
+	}
+
+	pub fn get(&self, key: &str) -> &Json {
+		match self {
+			Json::Obj(fields) => fields
+				.iter()
+				.find(|(k, _)| k == key)
+				.map_or(&Json::Null, |(_, v)| v),
+			_ => &Json::Null,
+		}
+	}
+
+	pub fn as_str(&self) -> Option<&str> {
+		match self {
+			Json::Str(s) => Some(s),
+			_ => None,
+		}
+	}
+
+	pub fn as_i64(&self) -> Option<i64> {
+		match *self {
+			Json::Num(n) if n.fract() == 0.0 => Some(n as i64),
+			_ => None,
+		}
+	}
+
+	pub fn as_bool(&self) -> Option<bool> {
+		match *self {
+			Json::Bool(b) => Some(b),
+			_ => None,
+		}
+	}
+
+	pub fn as_array(&self) -> &[Json] {
+		match self {
+			Json::Arr(v) => v,
+			_ => &[],
+		}
+	}
+
+	pub fn parse(src: &str) -> Result<Json, String> {
+		let mut p = Parser {
+			src: src.as_bytes(),
+			pos: 0,
+		};
+		let val = p.value()?;
+		p.space();
+		if p.pos < p.src.len() {
+			return Err(format!("trailing characters at {}", p.pos));
+		}
+		Ok(val)
+	}
+}
+
+fn quote(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+	f.write_char('"')?;
+	for c in s.chars() {
+		match c {
+			'"' => f.write_str("\\\"")?,
+			'\\' => f.write_str("\\\\")?,
+			'\n' => f.write_str("\\n")?,
+			'\r' => f.write_str("\\r")?,
+			'\t' => f.write_str("\\t")?,
+			c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+			c => f.write_char(c)?,
+		}
+	}
+	f.write_char('"')
+}
+
+impl fmt::Display for Json {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Json::Null => f.write_str("null"),
+			Json::Bool(b) => write!(f, "{}", b),
+			Json::Num(n) if n.fract() == 0.0 && n.abs() < 1e15 => write!(f, "{}", *n as i64),
+			Json::Num(n) if n.is_finite() => write!(f, "{}", n),
+			Json::Num(_) => f.write_str("null"),
+			Json::Str(s) => quote(f, s),
+			Json::Arr(v) => {
+				f.write_char('[')?;
+				for (i, val) in v.iter().enumerate() {
+					if i > 0 {
+						f.write_char(',')?;
+					}
+					write!(f, "{}", val)?;
+				}
+				f.write_char(']')
+			}
+			Json::Obj(fields) => {
+				f.write_char('{')?;
+				for (i, (k, val)) in fields.iter().enumerate() {
+					if i > 0 {
+						f.write_char(',')?;
+					}
+					quote(f, k)?;
+					write!(f, ":{}", val)?;
+				}
+				f.write_char('}')
+			}
+		}
+	}
+}
+
+pub fn read(input: &mut dyn BufRead) -> io::Result<Option<Json>> {
+	let mut len = None;
+	let mut line = String::new();
+	loop {
+		line.clear();
+		if input.read_line(&mut line)? == 0 {
+			return Ok(None);
+		}
+		let header = line.trim_end();
+		if header.is_empty() {
+			if len.is_some() {
+				break;
+			}
+		} else if let Some(n) = header.strip_prefix("Content-Length:") {
+			len = n.trim().parse::<usize>().ok();
+		}
+	}
+	let mut buf = vec![0; len.unwrap_or(0)];
+	input.read_exact(&mut buf)?;
+	Json::parse(&String::from_utf8_lossy(&buf))
+		.map(Some)
+		.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+pub fn write(out: &mut dyn Write, msg: &Json) -> io::Result<()> {
+	let text = msg.to_string();
+	write!(out, "Content-Length: {}\r\n\r\n{}", text.len(), text)?;
+	out.flush()
+}
+
+struct Parser<'a> {
+	src: &'a [u8],
+	pos: usize,
+}
+
+impl<'a> Parser<'a> {
+	fn space(&mut self) {
+		while self.pos < self.src.len() && self.src[self.pos].is_ascii_whitespace() {
+			self.pos += 1;
+		}
+	}
+
+	fn peek(&mut self) -> Option<u8> {
+		self.space();
+		self.src.get(self.pos).copied()
+	}
+
+	fn expect(&mut self, c: u8) -> Result<(), String> {
+		if self.peek() == Some(c) {
+			self.pos += 1;
+			Ok(())
+		} else {
+			Err(format!("expected '{}' at {}", c as char, self.pos))
+		}
+	}
+
+	fn word(&mut self, word: &str, val: Json) -> Result<Json, String> {
+		if self.src[self.pos..].starts_with(word.as_bytes()) {
+			self.pos += word.len();
+			Ok(val)
+		} else {
+			Err(format!("unexpected character at {}", self.pos))
+		}
+	}
+
+	fn value(&mut self) -> Result<Json, String> {
+		match self.peek() {
+			Some(b'n') => self.word("null", Json::Null),
+			Some(b't') => self.word("true", Json::Bool(true)),
+			Some(b'f') => self.word("false", Json::Bool(false)),
+			Some(b'"') => self.string().map(Json::Str),
+			Some(b'[') => {
+				self.pos += 1;
+				let mut v = Vec::new();
+				if self.peek() == Some(b']') {
+					self.pos += 1;
+					return Ok(Json::Arr(v));
+				}
+				loop {
+					v.push(self.value()?);
+					match self.peek() {
+						Some(b',') => self.pos += 1,
+						_ => break,
+					}
+				}
+				self.expect(b']')?;
+				Ok(Json::Arr(v))
+			}
+			Some(b'{') => {
+				self.pos += 1;
+				let mut fields = Vec::new();
+				if self.peek() == Some(b'}') {
+					self.pos += 1;
+					return Ok(Json::Obj(fields));
+				}
+				loop {
+					self.space();
+					let key = self.string()?;
+					self.expect(b':')?;
+					fields.push((key, self.value()?));
+					match self.peek() {
+						Some(b',') => self.pos += 1,
+						_ => break,
+					}
+				}
+				self.expect(b'}')?;
+				Ok(Json::Obj(fields))
+			}
+			Some(b'-') | Some(b'0'..=b'9') => {
+				let start = self.pos;
+				while self.pos < self.src.len()
+					&& matches!(
+						self.src[self.pos],
+						b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9'
+					) {
+					self.pos += 1;
+				}
+				let text = String::from_utf8_lossy(&self.src[start..self.pos]);
+				text.parse()
+					.map(Json::Num)
+					.map_err(|_| format!("bad number at {}", start))
+			}
+			Some(_) => Err(format!("unexpected character at {}", self.pos)),
+			None => Err(String::from("unexpected end of input")),
+		}
+	}
+
+	fn hex(&mut self) -> Result<u32, String> {
+		let digits = self
+			.src
+			.get(self.pos..self.pos + 4)
+			.and_then(|d| std::str::from_utf8(d).ok())
+			.and_then(|d| u32::from_str_radix(d, 16).ok())
+			.ok_or_else(|| format!("bad escape at {}", self.pos))?;
+		self.pos += 4;
+		Ok(digits)
+	}
+
+	fn string(&mut self) -> Result<String, String> {
+		self.expect(b'"')?;
+		let mut buf = Vec::new();
+		loop {
+			let c = *self
+				.src
+				.get(self.pos)
+				.ok_or_else(|| String::from("unterminated string"))?;
+			self.pos += 1;
+			match c {
+				b'"' => break,
+				b'\\' => {
+					let e = *self
+						.src
+						.get(self.pos)
+						.ok_or_else(|| String::from("unterminated string"))?;
+					self.pos += 1;
+					let c = match e {
+						b'n' => '\n',
+						b't' => '\t',
+						b'r' => '\r',
+						b'b' => '\u{8}',
+						b'f' => '\u{c}',
+						b'u' => {
+							let hi = self.hex()?;
+							let code = if (0xd800..0xdc00).contains(&hi)
+								&& self.src[self.pos..].starts_with(b"\\u")
+							{
+								self.pos += 2;
+								let lo = self.hex()?;
+								0x10000 + ((hi - 0xd800) << 10) + (lo.wrapping_sub(0xdc00) & 0x3ff)
+							} else {
+								hi
+							};
+							char::from_u32(code).unwrap_or('\u{fffd}')
+						}
+						e => e as char,
+					};
+					let mut tmp = [0; 4];
+					buf.extend_from_slice(c.encode_utf8(&mut tmp).as_bytes());
+				}
+				c => buf.push(c),
+			}
+		}
+		String::from_utf8(buf).map_err(|_| String::from("invalid utf-8 in string"))
+	}
+}
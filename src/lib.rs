@@ -0,0 +1,15 @@
+pub mod dap;
+pub mod debug;
+pub mod error;
+pub mod gas;
+pub mod json;
+pub mod loader;
+pub mod lsp;
+pub mod packed;
+pub mod policy;
+pub mod repl;
+pub mod runner;
+pub mod session;
+mod stdlib;
+pub mod tape;
+pub mod value;
@@ -0,0 +1,233 @@
+use fxhash::FxHashMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::rc::Rc;
+
+use crate::error::Error;
+use crate::packed::{self, Packed};
+use crate::stdlib::{gen_libs, is_embedded};
+use crate::tape::Image;
+use crate::value::{is_num, quote, unquote, Value};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Shadow {
+	Local,
+	Stdlib,
+}
+
+pub enum Module {
+	File(PathBuf),
+	Stdlib(Image),
+}
+
+pub const TYPED: &str = "#oil typed";
+
+pub fn parse(text: &str) -> FxHashMap<Value, Value> {
+	let typed = text
+		.strip_prefix(TYPED)
+		.is_some_and(|rest| rest.starts_with('\n'));
+	let mut tape = FxHashMap::default();
+	for (idx, line) in text.split_terminator('\n').skip(typed as usize).enumerate() {
+		let val = if !typed {
+			Value::from(line)
+		} else if line.is_empty() {
+			continue;
+		} else {
+			unquote(line).unwrap_or_else(|| Value::from(line))
+		};
+		tape.insert(Value::I(idx as i64), val);
+	}
+	tape
+}
+
+pub fn read(path: &Path) -> io::Result<Packed> {
+	let data = fs::read(path)?;
+	if data.starts_with(packed::MAGIC) {
+		return Packed::parse(&data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e));
+	}
+	match String::from_utf8(data) {
+		Ok(text) => Ok(Packed::new(parse(&text))),
+		Err(e) => Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+	}
+}
+
+pub fn load(path: &Path) -> io::Result<FxHashMap<Value, Value>> {
+	read(path).map(|image| image.cells)
+}
+
+fn plain(val: &Value) -> Option<String> {
+	let s = val.to_string();
+	if Value::from(&s[..]) == *val && !s.contains('\n') {
+		Some(s)
+	} else {
+		None
+	}
+}
+
+pub fn encode(cells: &[Value]) -> String {
+	let mut text = String::new();
+	let typed = cells.first().is_some_and(|val| val.to_string() == TYPED)
+		|| cells.iter().any(|val| plain(val).is_none());
+	if typed {
+		text.push_str(TYPED);
+		text.push('\n');
+	}
+	for val in cells {
+		match (plain(val), val) {
+			(Some(s), Value::S(n)) if is_num(n) => text.push_str(&s),
+			(Some(s), Value::I(_)) => text.push_str(&s),
+			(Some(s), _) if !typed => text.push_str(&s),
+			_ => text.push_str(&quote(val)),
+		}
+		text.push('\n');
+	}
+	text
+}
+
+fn is_module(name: &str) -> bool {
+	!name.is_empty()
+		&& name
+			.bytes()
+			.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == b'_')
+}
+
+pub struct Loader {
+	pub stdlib: FxHashMap<String, Image>,
+	pub modcache: FxHashMap<PathBuf, Image>,
+	pub search: Vec<PathBuf>,
+	pub shadow: Shadow,
+	pub sandbox: Option<Vec<PathBuf>>,
+	pub file_modules: bool,
+}
+
+impl Default for Loader {
+	fn default() -> Loader {
+		Loader::new()
+	}
+}
+
+impl Loader {
+	pub fn new() -> Loader {
+		Loader {
+			stdlib: gen_libs(),
+			modcache: FxHashMap::default(),
+			search: Vec::new(),
+			shadow: Shadow::Local,
+			sandbox: None,
+			file_modules: true,
+		}
+	}
+
+	pub fn add_env_path(&mut self) {
+		if let Some(paths) = env::var_os("OIL_PATH") {
+			self.search
+				.extend(env::split_paths(&paths).filter(|p| !p.as_os_str().is_empty()));
+		}
+	}
+
+	pub fn add_lib_dir(&mut self, dir: &Path) -> io::Result<Vec<String>> {
+		let mut shadowed = Vec::new();
+		let mut names = Vec::new();
+		for ent in fs::read_dir(dir)? {
+			let ent = ent?;
+			if let Ok(name) = ent.file_name().into_string() {
+				if is_module(&name) && ent.path().is_file() {
+					names.push(name);
+				}
+			}
+		}
+		names.sort();
+		for name in names {
+			let image = Rc::new(load(&dir.join(&name))?);
+			if self.stdlib.insert(name.clone(), image).is_some() && is_embedded(&name) {
+				shadowed.push(name);
+			}
+		}
+		Ok(shadowed)
+	}
+
+	pub fn confine(&mut self, roots: impl IntoIterator<Item = PathBuf>) {
+		self.sandbox = Some(
+			roots
+				.into_iter()
+				.filter_map(|root| root.canonicalize().ok())
+				.collect(),
+		);
+	}
+
+	fn check_name(&self, name: &Path) -> Result<(), Error> {
+		if self.sandbox.is_some() {
+			for comp in name.components() {
+				match comp {
+					Component::ParentDir => {
+						return Err(Error::PathTraversal(name.display().to_string()))
+					}
+					Component::RootDir | Component::Prefix(_) => {
+						return Err(Error::AbsolutePath(name.display().to_string()))
+					}
+					_ => (),
+				}
+			}
+		}
+		Ok(())
+	}
+
+	fn find_file(&self, root: Option<&Path>, name: &Path) -> Result<Option<PathBuf>, Error> {
+		if !self.file_modules {
+			return Err(Error::FileModulesDisabled(name.display().to_string()));
+		}
+		self.check_name(name)?;
+		let path = root
+			.into_iter()
+			.chain(self.search.iter().map(PathBuf::as_path))
+			.map(|dir| dir.join(name))
+			.find(|path| path.is_file());
+		if let (Some(path), Some(roots)) = (&path, &self.sandbox) {
+			match path.canonicalize() {
+				Ok(ref canon) if roots.iter().any(|root| canon.starts_with(root)) => (),
+				_ => return Err(Error::OutsideSandbox(path.clone())),
+			}
+		}
+		Ok(path)
+	}
+
+	pub fn image(&mut self, path: &Path) -> Result<Image, Error> {
+		if let Some(image) = self.modcache.get(path) {
+			return Ok(image.clone());
+		}
+		let image = Rc::new(load(path).map_err(|e| Error::BadModule(path.to_path_buf(), e))?);
+		self.modcache.insert(path.to_path_buf(), image.clone());
+		Ok(image)
+	}
+
+	fn find_stdlib(&self, name: &str) -> Option<Module> {
+		self.stdlib
+			.get(name)
+			.map(|image| Module::Stdlib(image.clone()))
+	}
+
+	pub fn resolve(&self, root: Option<&Path>, name: &Value) -> Result<Option<Module>, Error> {
+		Ok(match *name {
+			Value::S(ref x) => {
+				if self.shadow == Shadow::Stdlib || !self.file_modules {
+					if let Some(m) = self.find_stdlib(x) {
+						return Ok(Some(m));
+					}
+				}
+				self.find_file(root, Path::new(&x[..]))?
+					.map(Module::File)
+					.or_else(|| self.find_stdlib(x))
+			}
+			Value::I(x) => self
+				.find_file(root, Path::new(&x.to_string()))?
+				.map(Module::File),
+			Value::C(x) => {
+				let mut buf = [0u8; 4];
+				self.find_file(root, Path::new(x.encode_utf8(&mut buf)))?
+					.map(Module::File)
+			}
+		})
+	}
+}
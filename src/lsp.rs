@@ -0,0 +1,443 @@
+use fxhash::FxHashMap;
+use std::io::{self, BufRead, Write};
+
+use crate::error::opname;
+use crate::gas::{self, Line};
+use crate::json::{self, Json};
+use crate::loader::Loader;
+
+const ERROR: i64 = 1;
+const WARNING: i64 = 2;
+
+struct Label<'a> {
+	line: usize,
+	start: usize,
+	name: &'a str,
+	def: bool,
+}
+
+fn width(s: &str) -> usize {
+	s.encode_utf16().count()
+}
+
+fn prefix(line: &str, character: usize) -> &str {
+	let mut n = 0;
+	for (i, c) in line.char_indices() {
+		if n >= character {
+			return &line[..i];
+		}
+		n += c.len_utf16();
+	}
+	line
+}
+
+fn range(line: usize, start: usize, end: usize) -> Json {
+	let pos = |character| {
+		Json::obj(vec![
+			("line", Json::from(line)),
+			("character", Json::from(character)),
+		])
+	};
+	Json::obj(vec![("start", pos(start)), ("end", pos(end))])
+}
+
+fn location(uri: &str, line: usize, start: usize, end: usize) -> Json {
+	Json::obj(vec![
+		("uri", Json::from(uri)),
+		("range", range(line, start, end)),
+	])
+}
+
+fn indent(line: &str) -> usize {
+	width(line) - width(line.trim_start_matches(&[' ', '\t'][..]))
+}
+
+fn label<'a>(n: usize, line: &str, parsed: &Line<'a>) -> Option<Label<'a>> {
+	let start = indent(line) + 1;
+	match *parsed {
+		Line::Label(name) => Some(Label {
+			line: n,
+			start,
+			name,
+			def: true,
+		}),
+		Line::Ref(name) => Some(Label {
+			line: n,
+			start,
+			name,
+			def: false,
+		}),
+		Line::Directive(".expect_cell", arg) => {
+			let name = arg.strip_prefix('$')?;
+			Some(Label {
+				line: n,
+				start: start + width(".expect_cell "),
+				name: &name[..name.find(' ').unwrap_or(name.len())],
+				def: false,
+			})
+		}
+		_ => None,
+	}
+}
+
+fn labels(src: &str) -> Vec<Label<'_>> {
+	src.lines()
+		.zip(&gas::lines(src))
+		.enumerate()
+		.filter_map(|(n, (line, parsed))| label(n, line, parsed))
+		.collect()
+}
+
+fn addresses(src: &str) -> Vec<i64> {
+	let mut addr = 0;
+	gas::lines(src)
+		.iter()
+		.map(|line| {
+			if let Line::Directive(".org", arg) = *line {
+				addr = arg.trim().parse().unwrap_or(addr).max(addr);
+			}
+			let at = addr;
+			match *line {
+				Line::Op(_)
+				| Line::Ref(_)
+				| Line::Str(_)
+				| Line::Typed(_)
+				| Line::Heredoc(_)
+				| Line::Literal(_) => addr += 1,
+				_ => (),
+			}
+			at
+		})
+		.collect()
+}
+
+fn diagnostic(line: usize, start: usize, end: usize, severity: i64, message: String) -> Json {
+	Json::obj(vec![
+		("range", range(line, start, end)),
+		("severity", Json::from(severity)),
+		("source", Json::from("oilrs")),
+		("message", Json::from(message)),
+	])
+}
+
+fn diagnostics(src: &str) -> Vec<Json> {
+	let mut out = Vec::new();
+	let labels = labels(src);
+	let mut defs = FxHashMap::default();
+	for l in labels.iter().filter(|l| l.def) {
+		let end = l.start + width(l.name);
+		if let Some(&first) = defs.get(l.name) {
+			out.push(diagnostic(
+				l.line,
+				l.start,
+				end,
+				ERROR,
+				format!(
+					"duplicate label: {}, first defined on line {}",
+					l.name,
+					first + 1
+				),
+			));
+		} else {
+			defs.insert(l.name, l.line);
+		}
+	}
+	for l in labels.iter().filter(|l| !l.def) {
+		if !defs.contains_key(l.name) {
+			let end = l.start + width(l.name);
+			out.push(diagnostic(
+				l.line,
+				l.start,
+				end,
+				ERROR,
+				format!("unknown label: {}", l.name),
+			));
+		}
+	}
+	let mut pending = 0;
+	for (n, (line, parsed)) in src.lines().zip(gas::lines(src)).enumerate() {
+		match parsed {
+			Line::Op(op) if pending == 0 => {
+				pending = gas::operands(op.parse().unwrap_or(0)).unwrap_or(0)
+			}
+			Line::Literal(word) if pending == 0 => match word.parse::<i64>() {
+				Ok(op) => pending = gas::operands(op).unwrap_or(0),
+				Err(_)
+					if !word.is_empty()
+						&& word.bytes().all(|c| c.is_ascii_lowercase() || c == b'_') =>
+				{
+					out.push(diagnostic(
+						n,
+						indent(line),
+						width(line),
+						WARNING,
+						format!("unknown mnemonic: {}, quote data with \"", word),
+					))
+				}
+				Err(_) => (),
+			},
+			Line::Op(_)
+			| Line::Ref(_)
+			| Line::Str(_)
+			| Line::Typed(_)
+			| Line::Heredoc(_)
+			| Line::Literal(_)
+				if pending > 0 =>
+			{
+				pending -= 1
+			}
+			_ => (),
+		}
+	}
+	out
+}
+
+fn after_call(src: &str, line: usize) -> bool {
+	let mut lines = gas::lines(src);
+	lines.truncate(line);
+	lines
+		.into_iter()
+		.rev()
+		.find(|l| !matches!(l, Line::Comment | Line::Label(_) | Line::Directive(..)))
+		.is_some_and(|l| matches!(l, Line::Op("14")))
+}
+
+fn completion(src: &str, line: usize, character: usize, loader: &Loader) -> Json {
+	let text = src.lines().nth(line).unwrap_or("");
+	let typed = prefix(text, character);
+	let item = |label: String, kind: i64, detail: String| {
+		Json::obj(vec![
+			("label", Json::from(&label[..])),
+			("kind", Json::from(kind)),
+			("detail", Json::from(detail)),
+			(
+				"textEdit",
+				Json::obj(vec![
+					("range", range(line, indent(typed), width(typed))),
+					("newText", Json::from(label)),
+				]),
+			),
+		])
+	};
+	let items = if typed.trim_start().starts_with('$') {
+		let mut names = labels(src)
+			.into_iter()
+			.filter(|l| l.def)
+			.map(|l| l.name)
+			.collect::<Vec<_>>();
+		names.sort_unstable();
+		names.dedup();
+		names
+			.into_iter()
+			.map(|name| item(format!("${}", name), 18, String::from("label")))
+			.collect()
+	} else if after_call(src, line) {
+		let mut names = loader.stdlib.keys().collect::<Vec<_>>();
+		names.sort();
+		names
+			.into_iter()
+			.map(|name| item(name.clone(), 9, String::from("stdlib module")))
+			.collect()
+	} else {
+		gas::MNEMONICS
+			.iter()
+			.map(|&(name, op)| item(String::from(name), 14, format!("opcode {}", op)))
+			.collect::<Vec<_>>()
+	};
+	Json::obj(vec![
+		("isIncomplete", Json::from(false)),
+		("items", Json::from(items)),
+	])
+}
+
+fn hover(src: &str, line: usize) -> Json {
+	let text = src.lines().nth(line).unwrap_or("");
+	let parsed = gas::lines(src)
+		.into_iter()
+		.nth(line)
+		.unwrap_or(Line::Comment);
+	let value = match (&parsed, label(line, text, &parsed)) {
+		(&Line::Op(op), _) => {
+			let code = op.parse().unwrap_or(0);
+			format!(
+				"opcode {}: {}, {} operand{}",
+				op,
+				opname(code),
+				gas::operands(code).unwrap_or(0),
+				if gas::operands(code) == Some(1) {
+					""
+				} else {
+					"s"
+				}
+			)
+		}
+		(_, Some(l)) => {
+			let addrs = addresses(src);
+			match labels(src).iter().find(|d| d.def && d.name == l.name) {
+				Some(d) => format!("label {} at address {}", l.name, addrs[d.line]),
+				None => format!("unknown label: {}", l.name),
+			}
+		}
+		_ => return Json::Null,
+	};
+	Json::obj(vec![
+		(
+			"contents",
+			Json::obj(vec![
+				("kind", Json::from("plaintext")),
+				("value", Json::from(value)),
+			]),
+		),
+		("range", range(line, 0, width(text))),
+	])
+}
+
+fn named(src: &str, line: usize) -> Option<&str> {
+	labels(src)
+		.into_iter()
+		.find(|l| l.line == line)
+		.map(|l| l.name)
+}
+
+fn definition(uri: &str, src: &str, line: usize) -> Json {
+	let name = match named(src, line) {
+		Some(name) => name,
+		None => return Json::Null,
+	};
+	match labels(src).into_iter().find(|l| l.def && l.name == name) {
+		Some(l) => location(uri, l.line, l.start, l.start + width(l.name)),
+		None => Json::Null,
+	}
+}
+
+fn references(uri: &str, src: &str, line: usize, declaration: bool) -> Json {
+	let name = match named(src, line) {
+		Some(name) => name,
+		None => return Json::Null,
+	};
+	Json::from(
+		labels(src)
+			.into_iter()
+			.filter(|l| l.name == name && (declaration || !l.def))
+			.map(|l| location(uri, l.line, l.start, l.start + width(l.name)))
+			.collect::<Vec<_>>(),
+	)
+}
+
+fn publish(out: &mut dyn Write, uri: &str, diagnostics: Vec<Json>) -> io::Result<()> {
+	json::write(
+		out,
+		&Json::obj(vec![
+			("jsonrpc", Json::from("2.0")),
+			("method", Json::from("textDocument/publishDiagnostics")),
+			(
+				"params",
+				Json::obj(vec![
+					("uri", Json::from(uri)),
+					("diagnostics", Json::from(diagnostics)),
+				]),
+			),
+		]),
+	)
+}
+
+fn capabilities() -> Json {
+	Json::obj(vec![
+		(
+			"capabilities",
+			Json::obj(vec![
+				("textDocumentSync", Json::from(1)),
+				("definitionProvider", Json::from(true)),
+				("referencesProvider", Json::from(true)),
+				("hoverProvider", Json::from(true)),
+				(
+					"completionProvider",
+					Json::obj(vec![(
+						"triggerCharacters",
+						Json::from(vec![Json::from("$")]),
+					)]),
+				),
+			]),
+		),
+		(
+			"serverInfo",
+			Json::obj(vec![
+				("name", Json::from("oilrs")),
+				("version", Json::from(env!("CARGO_PKG_VERSION"))),
+			]),
+		),
+	])
+}
+
+pub fn serve(input: &mut dyn BufRead, output: &mut dyn Write, loader: &Loader) -> io::Result<()> {
+	let mut docs: FxHashMap<String, String> = FxHashMap::default();
+	while let Some(msg) = json::read(input)? {
+		let method = msg.get("method").as_str().unwrap_or("");
+		let params = msg.get("params");
+		let uri = params.get("textDocument").get("uri").as_str().unwrap_or("");
+		let line = params
+			.get("position")
+			.get("line")
+			.as_i64()
+			.unwrap_or(0)
+			.max(0) as usize;
+		let character = params
+			.get("position")
+			.get("character")
+			.as_i64()
+			.unwrap_or(0)
+			.max(0) as usize;
+		let src = docs.get(uri).map_or("", |s| &s[..]);
+		let result = match method {
+			"initialize" => Ok(capabilities()),
+			"shutdown" => Ok(Json::Null),
+			"textDocument/completion" => Ok(completion(src, line, character, loader)),
+			"textDocument/hover" => Ok(hover(src, line)),
+			"textDocument/definition" => Ok(definition(uri, src, line)),
+			"textDocument/references" => Ok(references(
+				uri,
+				src,
+				line,
+				params
+					.get("context")
+					.get("includeDeclaration")
+					.as_bool()
+					.unwrap_or(true),
+			)),
+			"exit" => return Ok(()),
+			"textDocument/didOpen" | "textDocument/didChange" => {
+				let text = match params.get("contentChanges").as_array().last() {
+					Some(change) => change.get("text"),
+					None => params.get("textDocument").get("text"),
+				};
+				let text = String::from(text.as_str().unwrap_or(""));
+				publish(output, uri, diagnostics(&text))?;
+				docs.insert(String::from(uri), text);
+				continue;
+			}
+			"textDocument/didClose" => {
+				docs.remove(uri);
+				publish(output, uri, Vec::new())?;
+				continue;
+			}
+			_ => Err(format!("unsupported method: {}", method)),
+		};
+		let id = msg.get("id");
+		if *id == Json::Null {
+			continue;
+		}
+		let mut reply = vec![("jsonrpc", Json::from("2.0")), ("id", id.clone())];
+		match result {
+			Ok(result) => reply.push(("result", result)),
+			Err(message) => reply.push((
+				"error",
+				Json::obj(vec![
+					("code", Json::from(-32601)),
+					("message", Json::from(message)),
+				]),
+			)),
+		}
+		json::write(output, &Json::obj(reply))?;
+	}
+	Ok(())
+}
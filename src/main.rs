@@ -1,108 +1,687 @@
-mod stdlib;
-mod tape;
-mod value;
-
-use std::borrow::Cow;
 use std::env;
 use std::fs;
-use std::io::{BufRead, BufReader, Write};
-use std::path::Path;
+use std::io::{self, BufReader, BufWriter, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::process;
 
-use fxhash::FxHashMap;
-use tape::Tape;
-use value::Value;
+use oil_lang::dap;
+use oil_lang::debug;
+use oil_lang::error::Error;
+use oil_lang::gas::{self, Spelling};
+use oil_lang::loader::{self, Loader, Module, Shadow};
+use oil_lang::lsp;
+use oil_lang::packed::Packed;
+use oil_lang::policy::{Buffering, Eof, Input, Output, Policy, Random};
+use oil_lang::repl;
+use oil_lang::runner::{self, Outcome};
+use oil_lang::session::Session;
+use oil_lang::tape::{Tape, ARGS, ENV};
+use oil_lang::value::Value;
 
-fn main() {
-	if let Some(a) = env::args().nth(1) {
-		if let Some(b) = env::args().nth(2) {
-			if let Ok(f) = fs::File::open(&a) {
-				let f = BufReader::new(f);
-				let mut lines = Vec::new();
-				let mut labels = FxHashMap::default();
-				let mut labelfill = Vec::new();
-				for line in f.lines() {
-					if let Ok(line) = line {
-						let lineno = lines.len();
-						lines.push(
-							if let Some(op) = match &line[..] {
-								"nop" => Some("0"),
-								"copy" | "mov" => Some("1"),
-								"reverse" => Some("2"),
-								"quit" | "exit" | "return" => Some("3"),
-								"output" | "write" => Some("4"),
-								"user_input" | "read" => Some("5"),
-								"jump" | "jmp" => Some("6"),
-								"relative_jump" | "jr" => Some("7"),
-								"increment" | "+" => Some("8"),
-								"decrement" | "-" => Some("9"),
-								"conditional_jump" | "je" => Some("10"),
-								"newline" => Some("11"),
-								"explode" => Some("12"),
-								"implode" => Some("13"),
-								"call" => Some("14"),
-								"rand" => Some("15"),
-								"ord" => Some("16"),
-								"chr" => Some("17"),
-								_ => None,
-							} {
-								Cow::Borrowed(op)
-							} else if line.starts_with('$') {
-								labelfill.push(lineno);
-								Cow::Owned(line)
-							} else if line.starts_with(':') {
-								if let Some(oldidx) =
-									labels.insert(String::from(&line[1..]), lineno.to_string())
-								{
-									println!("Duplicate labels: {} {}", oldidx, lineno);
-								}
-								continue;
-							} else if line.starts_with('"') {
-								Cow::Owned(String::from(&line[1..]))
-							} else if line.starts_with('#') {
-								continue;
-							} else {
-								Cow::Owned(line)
-							},
-						);
-					}
-				}
-				if let Ok(mut output) = fs::File::create(&b) {
-					let mut labelidx = 0;
-					for (idx, line) in lines.into_iter().enumerate() {
-						if labelidx < labelfill.len() && labelfill[labelidx] == idx {
-							labelidx += 1;
-							if let Some(lineno) = labels.get(&line[1..]) {
-								writeln!(output, "{}", lineno).ok();
-								continue;
-							} else {
-								println!("Unknown label: {}", &line[1..]);
-							}
+fn fail(path: &str, e: impl std::fmt::Display) -> ! {
+	eprintln!("{}: {}", path, e);
+	process::exit(1);
+}
+
+fn read(path: &str) -> String {
+	fs::read_to_string(path).unwrap_or_else(|e| fail(path, e))
+}
+
+fn write(path: &str, data: impl AsRef<[u8]>) {
+	if let Err(e) = fs::write(path, data) {
+		fail(path, e);
+	}
+}
+
+fn assemble(src: &str, dst: Option<&str>, image: Option<bool>) {
+	let asm = gas::assemble(&read(src));
+	for warning in &asm.warnings {
+		eprintln!("{}: {}", src, warning);
+	}
+	let dst = match dst {
+		Some(dst) => PathBuf::from(dst),
+		None => Path::new(src).with_extension("oil"),
+	};
+	let data = match image {
+		Some(strip) => {
+			let mut image = Packed::new(asm.tape());
+			if !strip {
+				image.symbols = asm.labels;
+			}
+			let mut data = Vec::new();
+			image.write(&mut data).unwrap_or_else(|e| fail(src, e));
+			data
+		}
+		None if !asm.dense() => fail(
+			src,
+			".org leaves gaps in the tape, assemble it with --image",
+		),
+		None => loader::encode(&asm.cells).into_bytes(),
+	};
+	write(&dst.to_string_lossy(), data);
+}
+
+fn disassemble(src: &str, dst: Option<&str>) {
+	let image = loader::read(Path::new(src)).unwrap_or_else(|e| fail(src, e));
+	let (lines, warnings) = gas::disassemble(&image.cells, &image.symbols);
+	for warning in warnings {
+		eprintln!("{}: {}", src, warning);
+	}
+	let mut text = String::new();
+	for line in lines {
+		text.push_str(&line);
+		text.push('\n');
+	}
+	match dst {
+		Some(dst) => write(dst, &text),
+		None => print!("{}", text),
+	}
+}
+
+fn check(paths: &[String], loader: &mut Loader, sandbox: bool) {
+	let mut problems = 0;
+	for src in paths {
+		let path = Path::new(src);
+		let cells = if path.extension().is_some_and(|ext| ext == "gas") {
+			let asm = gas::assemble(&read(src));
+			for warning in &asm.warnings {
+				eprintln!("{}: {}", src, warning);
+			}
+			problems += asm.warnings.len();
+			asm.tape()
+		} else {
+			loader::load(path).unwrap_or_else(|e| fail(src, e))
+		};
+		let root = path.parent().unwrap_or_else(|| Path::new(""));
+		confine(loader, sandbox, root);
+		let mut addrs = cells
+			.keys()
+			.filter_map(|addr| match *addr {
+				Value::I(a) if a >= 0 => Some(a),
+				_ => None,
+			})
+			.collect::<Vec<_>>();
+		addrs.sort_unstable();
+		let mut next = 0;
+		for addr in addrs {
+			if addr < next {
+				continue;
+			}
+			let cell = &cells[&Value::I(addr)];
+			let n = match *cell {
+				Value::I(op) => gas::operands(op).unwrap_or(0),
+				_ => 0,
+			};
+			if *cell == Value::I(14) {
+				if let Some(name) = cells.get(&Value::I(addr + 1)) {
+					match loader.resolve(Some(root), name) {
+						Ok(Some(_)) => (),
+						Ok(None) => {
+							eprintln!("{}: call {} at {}: module not found", src, name, addr);
+							problems += 1;
+						}
+						Err(e) => {
+							eprintln!("{}: {} at {}", src, e, addr);
+							problems += 1;
 						}
-						writeln!(output, "{}", line).ok();
 					}
 				}
 			}
+			next = addr.saturating_add(1 + n as i64);
+		}
+	}
+	if problems > 0 {
+		process::exit(1);
+	}
+}
+
+fn format(paths: &[String], check: bool, spelling: Spelling) {
+	let mut changed = false;
+	for src in paths {
+		let text = read(src);
+		let formatted = gas::format(&text, spelling);
+		if formatted != text {
+			changed = true;
+			if check {
+				println!("{}", src);
+			} else {
+				write(src, &formatted);
+			}
+		}
+	}
+	if check && changed {
+		process::exit(1);
+	}
+}
+
+fn confine(loader: &mut Loader, sandbox: bool, root: &Path) {
+	if sandbox {
+		let root = if root.as_os_str().is_empty() {
+			Path::new(".")
 		} else {
-			let path = Path::new(&a);
-			let mut tape = Tape::new(path.parent());
-			if let Ok(f) = fs::File::open(&path) {
-				let mut f = BufReader::new(f);
-				let mut line = String::new();
-				let mut idx = 0;
-				while let Ok(n) = f.read_line(&mut line) {
-					if n == 0 {
-						break;
+			root
+		};
+		let mut roots = vec![root.to_path_buf()];
+		roots.extend(loader.search.iter().cloned());
+		loader.confine(roots);
+	}
+}
+
+fn load(src: &str, args: &[String], env: &[String], loader: &mut Loader, sandbox: bool) -> Tape {
+	let path = Path::new(src);
+	confine(
+		loader,
+		sandbox,
+		path.parent().unwrap_or_else(|| Path::new("")),
+	);
+	let mut tape = Tape::new(path.parent());
+	match runner::program(path) {
+		Ok((cells, _)) => tape.tape = cells,
+		Err(e) => fail(src, e),
+	}
+	tape.set_list(ARGS, args);
+	tape.set_list(ENV, env);
+	tape
+}
+
+enum Log {
+	Off,
+	Record(String),
+	Replay(String, Vec<u8>),
+}
+
+fn run(
+	src: &str,
+	args: &[String],
+	env: &[String],
+	loader: &mut Loader,
+	policy: &mut Policy,
+	sandbox: bool,
+	log: &Log,
+) {
+	let mut tape = load(src, args, env, loader, sandbox);
+	let result = tape.run(loader, policy);
+	let flushed = policy.flush().map_err(Error::Write);
+	match (log, policy.record.take()) {
+		(Log::Record(path), Some(session)) => {
+			let mut text = Vec::new();
+			session.write(&mut text).ok();
+			if let Err(e) = fs::write(path, text) {
+				fail(path, e);
+			}
+		}
+		(Log::Replay(path, expected), Some(session)) if session.output != *expected => {
+			eprintln!("{}: output differs from the recording", path);
+			let expected = String::from_utf8_lossy(expected);
+			for line in runner::diff(&expected, &String::from_utf8_lossy(&session.output)) {
+				eprintln!("  {}", line);
+			}
+			process::exit(1);
+		}
+		_ => (),
+	}
+	match result.and_then(|status| flushed.map(|_| status)) {
+		Ok(0) => (),
+		Ok(status) => process::exit(status.clamp(1, 255) as i32),
+		Err(Error::Write(ref e)) if e.kind() == io::ErrorKind::BrokenPipe => (),
+		Err(e) => {
+			eprintln!("{}", e);
+			process::exit(1);
+		}
+	}
+}
+
+fn dap(loader: &mut Loader, policy: &mut Policy, sandbox: bool) {
+	confine(loader, sandbox, Path::new(""));
+	if let Err(e) = dap::serve(
+		&mut io::stdin().lock(),
+		&mut io::stdout().lock(),
+		loader,
+		policy,
+	) {
+		eprintln!("dap: {}", e);
+		process::exit(1);
+	}
+}
+
+fn which(name: &str, loader: &mut Loader, sandbox: bool) {
+	confine(loader, sandbox, Path::new(""));
+	match loader.resolve(Some(Path::new("")), &Value::from(name)) {
+		Ok(Some(Module::File(path))) => println!("{}", path.display()),
+		Ok(Some(Module::Stdlib(_))) => println!("stdlib:{}", name),
+		Ok(None) => {
+			eprintln!("{}: not found", name);
+			process::exit(1);
+		}
+		Err(e) => {
+			eprintln!("{}", e);
+			process::exit(1);
+		}
+	}
+}
+
+fn test(paths: &[String], loader: &mut Loader, policy: &mut Policy, sandbox: bool) {
+	policy.step_limit = policy.step_limit.or(Some(10_000_000));
+	let mut cases = Vec::new();
+	for path in paths {
+		match runner::discover(Path::new(path)) {
+			Ok(found) => cases.extend(found),
+			Err(e) => {
+				eprintln!("{}: {}", path, e);
+				process::exit(1);
+			}
+		}
+	}
+	let (mut passed, mut skipped) = (0, 0);
+	let mut failures = Vec::new();
+	for path in cases {
+		confine(
+			loader,
+			sandbox,
+			path.parent().unwrap_or_else(|| Path::new("")),
+		);
+		for case in runner::run_file(&path, loader, policy) {
+			print!("test {} ... ", case.name);
+			match case.outcome {
+				Outcome::Pass => {
+					println!("ok");
+					passed += 1;
+				}
+				Outcome::Skip => {
+					println!("skipped, no .out file or .test blocks");
+					skipped += 1;
+				}
+				Outcome::Fail(diff) => {
+					println!("FAILED");
+					failures.push((case.name, diff));
+				}
+				Outcome::Error(e) => {
+					println!("FAILED");
+					failures.push((case.name, vec![e]));
+				}
+			}
+		}
+	}
+	if !failures.is_empty() {
+		println!();
+		println!("failures:");
+		for (name, lines) in &failures {
+			println!("{}:", name);
+			for line in lines {
+				println!("  {}", line);
+			}
+		}
+	}
+	println!();
+	println!(
+		"test result: {}. {} passed; {} failed; {} skipped",
+		if failures.is_empty() { "ok" } else { "FAILED" },
+		passed,
+		failures.len(),
+		skipped
+	);
+	if !failures.is_empty() {
+		process::exit(1);
+	}
+}
+
+fn numarg<T: std::str::FromStr>(flag: &str, arg: Option<String>) -> T {
+	match arg.as_ref().map(|arg| arg.parse()) {
+		Some(Ok(n)) => n,
+		_ => {
+			eprintln!("{} expects a number", flag);
+			process::exit(1);
+		}
+	}
+}
+
+const USAGE: &str = "\
+usage: oilrs <command> [options] [args]
+
+commands:
+  run       execute an oil or gas program
+  asm       assemble gas into oil
+  disasm    print an oil program as gas
+  check     report problems in oil and gas programs without running them
+  test      run programs against their .in/.out files and .test blocks
+  debug     step through a program
+  dap       serve the debug adapter protocol on stdin and stdout
+  fmt       normalise gas source
+  lsp       serve the language server protocol for gas files on stdin and stdout
+  repl      assemble and run gas lines interactively on a live tape
+  which     print the module a call resolves to
+
+oilrs <command> --help shows the options of a command,
+oilrs file [args] is short for oilrs run file [args],
+oilrs file.gas file.oil is kept from older versions as oilrs asm file.gas file.oil";
+
+const OPTIONS: &str = "
+  -I dir               search dir for call modules, before OIL_PATH
+  --lib-dir dir        load dir's modules as stdlib, later dirs override
+  --no-shadow          stdlib names take precedence over files
+  --sandbox            confine call to the program dir and search path
+  --no-file-modules    only allow stdlib modules in call
+  --deny op            deny read, output, rand or call
+  --allow-call name    only allow call to listed modules
+  --max-output bytes   stop once output exceeds bytes
+  --seed n             seed rand deterministically
+  --eof policy         at end of input read empty, quit, flag=addr or sentinel=value
+  --bytes              read single bytes as 0-255, output 0-255 as raw bytes
+  --unbuffered         write output as soon as it is produced
+  --line-buffered      write output at each newline, the default on a terminal
+  --record file        save input read, the rand seed and output to file
+  --replay file        run again with a recording's input and seed, check output
+  --exit-cell addr     exit with the int in addr when the program ends
+  --max-steps n        stop after n steps, 10000000 per test by default";
+
+fn help(cmd: &str) -> Option<String> {
+	let (usage, options) = match cmd {
+		"run" => (
+			"usage: oilrs run [options] file [args]... [-- args...]\n\
+			 execute an oil or gas program, args are stored from cell -1 down,\n\
+			 count first, --env variables likewise from cell -1000000 as name=value,\n\
+			 or as a bare name when unset\n\noptions:\n  \
+			 --env name           expose environment variable name, repeatable\n  \
+			 --input file         read input from file instead of stdin\n  \
+			 --output file        write output to file instead of stdout",
+			true,
+		),
+		"asm" => (
+			"usage: oilrs asm [--image [--strip]] file.gas [-o file.oil]\n\
+			 assemble gas into oil, next to the source unless -o is given\n\noptions:\n  \
+			 --image              write a binary image with a label symbol table\n  \
+			 --strip              leave the symbol table out of the image",
+			false,
+		),
+		"disasm" => (
+			"usage: oilrs disasm file.oil [-o file.gas]\n\
+			 print an oil program as gas, to stdout unless -o is given",
+			false,
+		),
+		"check" => (
+			"usage: oilrs check [options] file...\n\
+			 report assembler warnings and calls to missing modules, exit 1 if any",
+			true,
+		),
+		"test" => (
+			"usage: oilrs test [options] path...\n\
+			 run programs against their .in and .out files and .test blocks",
+			true,
+		),
+		"debug" => (
+			"usage: oilrs debug [options] file [args]... [-- args...]\n\
+			 step through a program, type help at the prompt for commands\n\noptions:\n  \
+			 --env name           expose environment variable name, repeatable\n  \
+			 --input file         read program input from file instead of stdin\n  \
+			 --output file        write program output to file instead of stdout",
+			true,
+		),
+		"dap" => (
+			"usage: oilrs dap [options]\n\
+			 debug programs from an editor over the debug adapter protocol on stdio,\n\
+			 launch takes program, args, input lines and stopOnEntry",
+			true,
+		),
+		"lsp" => (
+			"usage: oilrs lsp [options]\n\
+			 check gas files in an editor over the language server protocol on stdio,\n\
+			 --lib-dir modules are offered as completions after call",
+			true,
+		),
+		"fmt" => (
+			"usage: oilrs fmt [--check] [--mnemonics canonical|short] file.gas...\n\
+			 spell mnemonics consistently and indent mnemonic and $label operands under\n\
+			 their instruction, data lines and comments are kept as written since leading\n\
+			 whitespace is part of their cell\n\noptions:\n  \
+			 --check              list files that would change, exit 1 if any\n  \
+			 --mnemonics style    canonical names like copy, the default, or short ones like mov",
+			false,
+		),
+		"repl" => (
+			"usage: oilrs repl [options]\n\
+			 assemble and run gas lines interactively on a live tape",
+			true,
+		),
+		"which" => (
+			"usage: oilrs which [options] name\n\
+			 print the module a call to name resolves to",
+			true,
+		),
+		_ => return None,
+	};
+	let mut s = String::from(usage);
+	if options && !usage.contains("options:") {
+		s.push_str("\n\noptions:");
+	}
+	if options {
+		s.push_str(OPTIONS);
+	}
+	Some(s)
+}
+
+fn usage(cmd: &str) -> ! {
+	eprintln!("{}", help(cmd).unwrap_or_else(|| String::from(USAGE)));
+	process::exit(2);
+}
+
+fn main() {
+	let mut loader = Loader::new();
+	let mut policy = Policy::default();
+	let mut sandbox = false;
+	let mut dst = None;
+	let mut fmt_check = false;
+	let mut spelling = Spelling::Canonical;
+	let mut image = None;
+	let mut vars = Vec::new();
+	let mut buffering = None;
+	let mut seed = None;
+	let (mut record, mut replay) = (None, None);
+	let mut args = Vec::new();
+	let mut argv = env::args().skip(1);
+	while let Some(arg) = argv.next() {
+		let cmd = args.first().map_or("", |cmd: &String| &cmd[..]);
+		if arg == "--" {
+			args.extend(argv.by_ref());
+		} else if arg == "--help" || arg == "-h" {
+			match help(cmd) {
+				Some(text) => println!("{}", text),
+				None => println!("{}", USAGE),
+			}
+			return;
+		} else if arg == "-I" {
+			if let Some(dir) = argv.next() {
+				loader.search.push(PathBuf::from(dir));
+			}
+		} else if let Some(dir) = arg.strip_prefix("-I") {
+			loader.search.push(PathBuf::from(dir));
+		} else if arg == "--lib-dir" {
+			if let Some(dir) = argv.next() {
+				match loader.add_lib_dir(Path::new(&dir)) {
+					Ok(shadowed) => {
+						for name in shadowed {
+							eprintln!(
+								"warning: {} overrides embedded stdlib module {}",
+								Path::new(&dir).join(&name).display(),
+								name
+							);
+						}
 					}
-					tape.tape
-						.insert(Value::I(idx), Value::from(line.trim_end_matches('\n')));
-					line.clear();
-					idx += 1;
+					Err(e) => fail(&dir, e),
 				}
 			}
-			tape.run();
+		} else if arg == "--no-shadow" {
+			loader.shadow = Shadow::Stdlib;
+		} else if arg == "--sandbox" {
+			sandbox = true;
+		} else if arg == "--no-file-modules" {
+			loader.file_modules = false;
+		} else if arg == "--deny" {
+			match argv.next().as_ref().map(|op| &op[..]) {
+				Some("read") => policy.input = Input::Deny,
+				Some("output") => policy.output = Output::Deny,
+				Some("rand") => policy.rand = Random::Deny,
+				Some("call") => policy.modules = Some(Default::default()),
+				_ => {
+					eprintln!("--deny expects one of read, output, rand, call");
+					process::exit(2);
+				}
+			}
+		} else if arg == "--allow-call" {
+			if let Some(name) = argv.next() {
+				policy.allow_module(name);
+			}
+		} else if arg == "--max-output" {
+			policy.output_limit = Some(numarg(&arg, argv.next()));
+		} else if arg == "--max-steps" {
+			policy.step_limit = Some(numarg(&arg, argv.next()));
+		} else if arg == "--env" {
+			let name = argv.next().unwrap_or_else(|| usage(cmd));
+			vars.push(match env::var_os(&name) {
+				Some(val) => format!("{}={}", name, val.to_string_lossy()),
+				None => name,
+			});
+		} else if arg == "--eof" {
+			let eof = argv.next().unwrap_or_default();
+			policy.eof = if eof == "empty" {
+				Eof::Empty
+			} else if eof == "quit" {
+				Eof::Quit
+			} else if let Some(addr) = eof.strip_prefix("flag=") {
+				Eof::Flag(Value::from(addr))
+			} else if let Some(val) = eof.strip_prefix("sentinel=") {
+				Eof::Sentinel(Value::from(val))
+			} else {
+				eprintln!("--eof expects one of empty, quit, flag=addr, sentinel=value");
+				process::exit(2);
+			};
+		} else if arg == "--unbuffered" {
+			buffering = Some(Buffering::Unbuffered);
+		} else if arg == "--line-buffered" {
+			buffering = Some(Buffering::Line);
+		} else if arg == "--record" {
+			record = Some(argv.next().unwrap_or_else(|| usage(cmd)));
+		} else if arg == "--replay" {
+			replay = Some(argv.next().unwrap_or_else(|| usage(cmd)));
+		} else if arg == "--bytes" {
+			policy.bytes = true;
+		} else if arg == "--exit-cell" {
+			let addr = argv.next().unwrap_or_else(|| usage(cmd));
+			policy.exit_cell = Some(Value::from(&addr[..]));
+		} else if arg == "--seed" {
+			seed = Some(numarg(&arg, argv.next()));
+		} else if arg == "--input" {
+			let path = argv.next().unwrap_or_else(|| usage(cmd));
+			let file = fs::File::open(&path).unwrap_or_else(|e| fail(&path, e));
+			policy.input = Input::Reader(Box::new(BufReader::new(file)));
+		} else if arg == "--output" {
+			let path = argv.next().unwrap_or_else(|| usage(cmd));
+			let file = fs::File::create(&path).unwrap_or_else(|e| fail(&path, e));
+			policy.output = Output::Writer(Box::new(BufWriter::new(file)));
+		} else if arg == "-o" {
+			dst = Some(argv.next().unwrap_or_else(|| usage(cmd)));
+		} else if arg == "--image" {
+			image = Some(image.unwrap_or(false));
+		} else if arg == "--strip" {
+			image = Some(true);
+		} else if arg == "--check" {
+			fmt_check = true;
+		} else if arg == "--mnemonics" {
+			spelling = match argv.next().as_deref() {
+				Some("canonical") => Spelling::Canonical,
+				Some("short") => Spelling::Short,
+				_ => usage(cmd),
+			};
+		} else if arg.starts_with('-') && arg.len() > 1 {
+			eprintln!("unknown option {}", arg);
+			usage(cmd);
+		} else {
+			args.push(arg);
+		}
+	}
+	loader.add_env_path();
+	let log = match (record, replay) {
+		(Some(path), None) => {
+			seed = seed.or_else(|| Some(rand::random()));
+			Log::Record(path)
+		}
+		(None, Some(path)) => {
+			let data = fs::read(&path).unwrap_or_else(|e| fail(&path, e));
+			let session = Session::parse(&data).unwrap_or_else(|e| fail(&path, e));
+			policy.input = Input::Reader(Box::new(io::Cursor::new(session.input)));
+			seed = Some(session.seed);
+			Log::Replay(path, session.output)
+		}
+		(Some(_), Some(_)) => {
+			eprintln!("--record and --replay cannot be combined");
+			process::exit(2);
 		}
+		(None, None) => Log::Off,
+	};
+	if let Some(seed) = seed {
+		if let Log::Record(_) | Log::Replay(..) = log {
+			policy.record = Some(Session::new(seed));
+		}
+		if !matches!(policy.rand, Random::Deny) {
+			policy.seed(seed);
+		}
+	}
+	policy.buffering = buffering.unwrap_or(if io::stdout().is_terminal() {
+		Buffering::Line
 	} else {
-		println!("oilrs [filename]: execute oil script");
-		println!("oilrs [gas-file] [oil-output]: compile gas-file to oil-output");
+		Buffering::Block
+	});
+	let dst = dst.as_deref();
+	match &args[..] {
+		[cmd, src, rest @ ..] if cmd == "run" => {
+			run(src, rest, &vars, &mut loader, &mut policy, sandbox, &log)
+		}
+		[cmd, src] if cmd == "asm" => assemble(src, dst, image),
+		[cmd, src, out] if cmd == "asm" && dst.is_none() => assemble(src, Some(out), image),
+		[cmd, src] if cmd == "disasm" => disassemble(src, dst),
+		[cmd, paths @ ..] if cmd == "check" && !paths.is_empty() => {
+			check(paths, &mut loader, sandbox)
+		}
+		[cmd, paths @ ..] if cmd == "test" && !paths.is_empty() => {
+			test(paths, &mut loader, &mut policy, sandbox)
+		}
+		[cmd, src, rest @ ..] if cmd == "debug" => {
+			let tape = load(src, rest, &vars, &mut loader, sandbox);
+			debug::debug(tape, &mut loader, &mut policy);
+		}
+		[cmd, paths @ ..] if cmd == "fmt" && !paths.is_empty() => {
+			format(paths, fmt_check, spelling)
+		}
+		[cmd] if cmd == "dap" => dap(&mut loader, &mut policy, sandbox),
+		[cmd] if cmd == "lsp" => {
+			if let Err(e) = lsp::serve(&mut io::stdin().lock(), &mut io::stdout().lock(), &loader) {
+				eprintln!("lsp: {}", e);
+				process::exit(1);
+			}
+		}
+		[cmd] if cmd == "repl" => {
+			confine(&mut loader, sandbox, Path::new(""));
+			repl::repl(&mut loader, &mut policy)
+		}
+		[cmd, name] if cmd == "which" => which(name, &mut loader, sandbox),
+		[cmd, ..] if help(cmd).is_some() => usage(cmd),
+		[src, out]
+			if dst.is_none()
+				&& Path::new(src).extension().is_some_and(|ext| ext == "gas")
+				&& Path::new(src).is_file() =>
+		{
+			eprintln!(
+				"oilrs: assembling {} into {}, use oilrs asm {} {}",
+				src, out, src, out
+			);
+			assemble(src, Some(out), None)
+		}
+		[src, rest @ ..] if Path::new(src).is_file() => {
+			run(src, rest, &vars, &mut loader, &mut policy, sandbox, &log)
+		}
+		[cmd, ..] => {
+			eprintln!("oilrs: {} is neither a command nor a file", cmd);
+			usage("")
+		}
+		[] => usage(""),
 	}
 }
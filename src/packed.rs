@@ -0,0 +1,196 @@
+use fxhash::FxHashMap;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use crate::value::{is_num, order, Value};
+
+pub const MAGIC: &[u8] = b"\x7foil";
+const VERSION: u8 = 1;
+
+const INT: u8 = 0;
+const CHAR: u8 = 1;
+const STRING: u8 = 2;
+const BIGNUM: u8 = 3;
+
+pub struct Packed {
+	pub cells: FxHashMap<Value, Value>,
+	pub symbols: Vec<(String, i64)>,
+}
+
+fn byte(data: &mut &[u8]) -> Result<u8, String> {
+	let (&b, rest) = data
+		.split_first()
+		.ok_or_else(|| String::from("image is truncated"))?;
+	*data = rest;
+	Ok(b)
+}
+
+fn varint(data: &mut &[u8]) -> Result<u64, String> {
+	let mut val = 0u64;
+	for shift in (0..64).step_by(7) {
+		let b = byte(data)?;
+		val |= ((b & 0x7f) as u64) << shift;
+		if b & 0x80 == 0 {
+			return Ok(val);
+		}
+	}
+	Err(String::from("varint is too long"))
+}
+
+fn int(data: &mut &[u8]) -> Result<i64, String> {
+	let val = varint(data)?;
+	Ok((val >> 1) as i64 ^ -((val & 1) as i64))
+}
+
+fn text(data: &mut &[u8]) -> Result<String, String> {
+	let len = varint(data)? as usize;
+	if data.len() < len {
+		return Err(String::from("image is truncated"));
+	}
+	let s = String::from_utf8(data[..len].to_vec()).map_err(|_| String::from("bad utf-8"))?;
+	*data = &data[len..];
+	Ok(s)
+}
+
+fn cell(data: &mut &[u8]) -> Result<Value, String> {
+	Ok(match byte(data)? {
+		INT => Value::I(int(data)?),
+		CHAR => match std::char::from_u32(varint(data)? as u32) {
+			Some(c) => Value::C(c),
+			None => return Err(String::from("bad char")),
+		},
+		STRING => Value::S(Rc::new(text(data)?)),
+		BIGNUM => {
+			let s = text(data)?;
+			if !is_num(&s) {
+				return Err(format!("bad bignum {}", s));
+			}
+			Value::S(Rc::new(s))
+		}
+		tag => return Err(format!("unknown cell tag {}", tag)),
+	})
+}
+
+fn put_varint(out: &mut Vec<u8>, mut val: u64) {
+	while val >= 0x80 {
+		out.push(val as u8 | 0x80);
+		val >>= 7;
+	}
+	out.push(val as u8);
+}
+
+fn put_int(out: &mut Vec<u8>, val: i64) {
+	put_varint(out, ((val << 1) ^ (val >> 63)) as u64);
+}
+
+fn put_text(out: &mut Vec<u8>, s: &str) {
+	put_varint(out, s.len() as u64);
+	out.extend_from_slice(s.as_bytes());
+}
+
+fn put_cell(out: &mut Vec<u8>, val: &Value) {
+	match *val {
+		Value::I(x) => {
+			out.push(INT);
+			put_int(out, x);
+		}
+		Value::C(c) => {
+			out.push(CHAR);
+			put_varint(out, c as u64);
+		}
+		Value::S(ref s) => {
+			out.push(if is_num(s) { BIGNUM } else { STRING });
+			put_text(out, s);
+		}
+	}
+}
+
+fn tag(val: &Value) -> u8 {
+	match *val {
+		Value::I(_) => INT,
+		Value::C(_) => CHAR,
+		Value::S(_) => STRING,
+	}
+}
+
+impl Packed {
+	pub fn new(cells: FxHashMap<Value, Value>) -> Packed {
+		Packed {
+			cells,
+			symbols: Vec::new(),
+		}
+	}
+
+	pub fn parse(mut data: &[u8]) -> Result<Packed, String> {
+		let data = &mut data;
+		match data.strip_prefix(MAGIC) {
+			Some(rest) => *data = rest,
+			None => return Err(String::from("not an oil image")),
+		}
+		let version = byte(data)?;
+		if version != VERSION {
+			return Err(format!("unsupported image version {}", version));
+		}
+		let mut cells = FxHashMap::default();
+		for _ in 0..varint(data)? {
+			let start = cell(data)?;
+			let count = varint(data)?;
+			match start {
+				Value::I(start) => {
+					for idx in 0..count {
+						let addr = start
+							.checked_add(idx as i64)
+							.ok_or_else(|| String::from("segment overflows"))?;
+						cells.insert(Value::I(addr), cell(data)?);
+					}
+				}
+				start if count == 1 => {
+					cells.insert(start, cell(data)?);
+				}
+				start => return Err(format!("segment at {} holds {} cells", start, count)),
+			}
+		}
+		let mut symbols = Vec::new();
+		for _ in 0..varint(data)? {
+			let name = text(data)?;
+			symbols.push((name, int(data)?));
+		}
+		if !data.is_empty() {
+			return Err(String::from("trailing bytes after image"));
+		}
+		Ok(Packed { cells, symbols })
+	}
+
+	pub fn write(&self, w: &mut dyn Write) -> io::Result<()> {
+		let mut addrs = self.cells.keys().collect::<Vec<_>>();
+		addrs.sort_by_key(|addr| (order(addr), tag(addr)));
+		let mut segments: Vec<(&Value, Vec<&Value>)> = Vec::new();
+		for addr in addrs {
+			let val = &self.cells[addr];
+			match (segments.last_mut(), addr) {
+				(Some((&Value::I(start), run)), &Value::I(a))
+					if a.checked_sub(start) == Some(run.len() as i64) =>
+				{
+					run.push(val)
+				}
+				_ => segments.push((addr, vec![val])),
+			}
+		}
+		let mut out = Vec::from(MAGIC);
+		out.push(VERSION);
+		put_varint(&mut out, segments.len() as u64);
+		for (start, run) in segments {
+			put_cell(&mut out, start);
+			put_varint(&mut out, run.len() as u64);
+			for val in run {
+				put_cell(&mut out, val);
+			}
+		}
+		put_varint(&mut out, self.symbols.len() as u64);
+		for (name, addr) in &self.symbols {
+			put_text(&mut out, name);
+			put_int(&mut out, *addr);
+		}
+		w.write_all(&out)
+	}
+}
@@ -0,0 +1,254 @@
+use fxhash::FxHashSet;
+use rand::rngs::{StdRng, ThreadRng};
+use rand::{thread_rng, RngCore, SeedableRng};
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, BufRead, BufWriter, Read, Stdout, Write};
+
+use crate::error::Error;
+use crate::session::Session;
+use crate::value::Value;
+
+pub enum Input {
+	Stdin,
+	Reader(Box<dyn BufRead>),
+	Buffer(VecDeque<String>),
+	Deny,
+}
+
+pub enum Output {
+	Stdout,
+	Writer(Box<dyn Write>),
+	Capture(Vec<u8>),
+	Deny,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Buffering {
+	Block,
+	Line,
+	Unbuffered,
+}
+
+#[derive(Clone)]
+pub enum Random {
+	Thread(ThreadRng),
+	Seeded(Box<StdRng>),
+	Deny,
+}
+
+#[derive(Clone)]
+pub enum Eof {
+	Empty,
+	Sentinel(Value),
+	Flag(Value),
+	Quit,
+}
+
+pub struct Policy {
+	pub input: Input,
+	pub eof: Eof,
+	pub bytes: bool,
+	pub pending: VecDeque<u8>,
+	pub output: Output,
+	pub stdout: BufWriter<Stdout>,
+	pub buffering: Buffering,
+	pub output_limit: Option<usize>,
+	pub written: usize,
+	pub rand: Random,
+	pub modules: Option<FxHashSet<String>>,
+	pub step_limit: Option<u64>,
+	pub steps: u64,
+	pub exit_cell: Option<Value>,
+	pub record: Option<Session>,
+}
+
+impl Default for Policy {
+	fn default() -> Policy {
+		Policy {
+			input: Input::Stdin,
+			eof: Eof::Empty,
+			bytes: false,
+			pending: VecDeque::new(),
+			output: Output::Stdout,
+			stdout: BufWriter::new(io::stdout()),
+			buffering: Buffering::Block,
+			output_limit: None,
+			written: 0,
+			rand: Random::Thread(thread_rng()),
+			modules: None,
+			step_limit: None,
+			steps: 0,
+			exit_cell: None,
+			record: None,
+		}
+	}
+}
+
+impl Policy {
+	pub fn seed(&mut self, seed: u64) {
+		self.rand = Random::Seeded(Box::new(StdRng::seed_from_u64(seed)));
+	}
+
+	pub fn allow_module(&mut self, name: String) {
+		self.modules
+			.get_or_insert_with(Default::default)
+			.insert(name);
+	}
+
+	pub fn tick(&mut self, addr: &Value) -> Result<(), Error> {
+		self.steps += 1;
+		match self.step_limit {
+			Some(limit) if self.steps > limit => Err(Error::StepLimit(limit, addr.clone())),
+			_ => Ok(()),
+		}
+	}
+
+	fn read_byte(&mut self) -> Option<u8> {
+		let mut b = [0];
+		let n = match self.input {
+			Input::Stdin => io::stdin().lock().read(&mut b).unwrap_or(0),
+			Input::Reader(ref mut reader) => reader.read(&mut b).unwrap_or(0),
+			Input::Buffer(ref mut lines) => {
+				if self.pending.is_empty() {
+					let line = lines.pop_front()?;
+					self.pending.extend(line.bytes());
+					self.pending.push_back(b'\n');
+				}
+				return self.pending.pop_front();
+			}
+			Input::Deny => 0,
+		};
+		if n == 0 {
+			None
+		} else {
+			Some(b[0])
+		}
+	}
+
+	fn log(&mut self, bytes: &[u8]) {
+		if let Some(ref mut session) = self.record {
+			session.input.extend_from_slice(bytes);
+		}
+	}
+
+	pub fn read(&mut self, addr: &Value) -> Result<Option<Value>, Error> {
+		if let Input::Deny = self.input {
+			return Err(Error::Denied(5, addr.clone()));
+		}
+		if let (Input::Stdin, Output::Stdout) = (&self.input, &self.output) {
+			self.stdout.flush().map_err(Error::Write)?;
+		}
+		if self.bytes {
+			let b = self.read_byte();
+			self.log(b.as_slice());
+			return Ok(b.map(|b| Value::I(b as i64)));
+		}
+		let mut buf = Vec::new();
+		let n = match self.input {
+			Input::Stdin => io::stdin().lock().read_until(b'\n', &mut buf).unwrap_or(0),
+			Input::Reader(ref mut reader) => reader.read_until(b'\n', &mut buf).unwrap_or(0),
+			Input::Buffer(ref mut lines) => match lines.pop_front() {
+				Some(line) => {
+					buf.extend(line.bytes());
+					buf.push(b'\n');
+					buf.len()
+				}
+				None => 0,
+			},
+			Input::Deny => 0,
+		};
+		if n == 0 {
+			return Ok(None);
+		}
+		self.log(&buf);
+		if buf.ends_with(b"\n") {
+			buf.pop();
+		}
+		Ok(Some(Value::from(&String::from_utf8_lossy(&buf)[..])))
+	}
+
+	pub fn eof(&self, empty: Value) -> Option<(Value, Option<&Value>)> {
+		match self.eof {
+			Eof::Empty => Some((empty, None)),
+			Eof::Sentinel(ref val) => Some((val.clone(), None)),
+			Eof::Flag(ref flag) => Some((empty, Some(flag))),
+			Eof::Quit => None,
+		}
+	}
+
+	fn writer(&mut self) -> Option<&mut dyn Write> {
+		match self.output {
+			Output::Stdout => Some(&mut self.stdout),
+			Output::Writer(ref mut w) => Some(w),
+			_ => None,
+		}
+	}
+
+	pub fn write(&mut self, op: i64, addr: &Value, args: fmt::Arguments) -> Result<(), Error> {
+		if self.output_limit.is_none()
+			&& self.record.is_none()
+			&& self.buffering == Buffering::Block
+		{
+			if let Some(w) = self.writer() {
+				return w.write_fmt(args).map_err(Error::Write);
+			}
+		}
+		self.write_bytes(op, addr, fmt::format(args).as_bytes())
+	}
+
+	pub fn write_bytes(&mut self, op: i64, addr: &Value, bytes: &[u8]) -> Result<(), Error> {
+		if let Output::Deny = self.output {
+			return Err(Error::Denied(op, addr.clone()));
+		}
+		self.written += bytes.len();
+		if let Some(limit) = self.output_limit {
+			if self.written > limit {
+				return Err(Error::OutputLimit(limit, op, addr.clone()));
+			}
+		}
+		if let Some(ref mut session) = self.record {
+			session.output.extend_from_slice(bytes);
+		}
+		if let Output::Capture(ref mut buf) = self.output {
+			buf.extend_from_slice(bytes);
+			return Ok(());
+		}
+		let flush = match self.buffering {
+			Buffering::Block => false,
+			Buffering::Line => bytes.contains(&b'\n'),
+			Buffering::Unbuffered => true,
+		};
+		if let Some(w) = self.writer() {
+			w.write_all(bytes).map_err(Error::Write)?;
+			if flush {
+				w.flush().map_err(Error::Write)?;
+			}
+		}
+		Ok(())
+	}
+
+	pub fn flush(&mut self) -> io::Result<()> {
+		match self.writer() {
+			Some(w) => w.flush(),
+			None => Ok(()),
+		}
+	}
+
+	pub fn rng(&mut self, addr: &Value) -> Result<&mut dyn RngCore, Error> {
+		Ok(match self.rand {
+			Random::Thread(ref mut rng) => rng,
+			Random::Seeded(ref mut rng) => rng,
+			Random::Deny => return Err(Error::Denied(15, addr.clone())),
+		})
+	}
+
+	pub fn check_call(&self, addr: &Value, name: &Value) -> Result<(), Error> {
+		match self.modules {
+			Some(ref allowed) if !allowed.contains(&name.to_string()) => {
+				Err(Error::ModuleDenied(name.to_string(), addr.clone()))
+			}
+			_ => Ok(()),
+		}
+	}
+}
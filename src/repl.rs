@@ -0,0 +1,236 @@
+use fxhash::FxHashMap;
+use std::io::{self, BufRead, Write};
+
+use crate::gas::{self, Line};
+use crate::loader::Loader;
+use crate::policy::Policy;
+use crate::tape::Tape;
+use crate::value::{order, show, Value};
+
+const OUT: i64 = 1000;
+const IN: i64 = 2000;
+
+const HELP: &str = "\
+gas lines are appended to the tape and run, several cells per line may be
+separated by spaces, a line starting with \" is a single string cell
+  #gas typed            read \"quoted\" and 'c' literals from here on
+  :label                define label at the next free cell
+  .set addr value       set a cell, addr may be a $label
+  .call name args...    call a module with args, \"quote\" args with spaces
+  .show [from [to]]     show cells, idx and dir
+  .help                 show this help
+  .quit                 leave the repl";
+
+pub struct Repl {
+	pub tape: Tape,
+	pub labels: FxHashMap<String, i64>,
+	pub end: i64,
+	pub typed: bool,
+}
+
+fn words(line: &str) -> Vec<&str> {
+	let mut words = Vec::new();
+	let mut rest = line.trim_start();
+	while !rest.is_empty() {
+		let len = if let Some(quoted) = rest.strip_prefix('"') {
+			quoted.find('"').map_or(rest.len(), |n| n + 2)
+		} else {
+			rest.find(char::is_whitespace).unwrap_or(rest.len())
+		};
+		words.push(&rest[..len]);
+		rest = rest[len..].trim_start();
+	}
+	words
+}
+
+fn unquote(word: &str) -> &str {
+	word.strip_prefix('"')
+		.map(|w| w.strip_suffix('"').unwrap_or(w))
+		.unwrap_or(word)
+}
+
+impl Default for Repl {
+	fn default() -> Repl {
+		Repl::new()
+	}
+}
+
+impl Repl {
+	pub fn new() -> Repl {
+		Repl {
+			tape: Tape::new(None),
+			labels: FxHashMap::default(),
+			end: 0,
+			typed: false,
+		}
+	}
+
+	fn addr(&self, word: &str) -> Result<Value, String> {
+		match word.strip_prefix('$') {
+			Some(label) => self
+				.labels
+				.get(label)
+				.map(|&n| Value::I(n))
+				.ok_or_else(|| format!("unknown label: {}", label)),
+			None => Ok(Value::from(word)),
+		}
+	}
+
+	pub fn append(
+		&mut self,
+		line: &str,
+		loader: &mut Loader,
+		policy: &mut Policy,
+	) -> Result<(), String> {
+		self.typed |= line.trim() == gas::TYPED;
+		let cells = if line.starts_with('"') || self.typed && line.starts_with('\'') {
+			vec![line]
+		} else {
+			line.split_whitespace().collect()
+		};
+		let mut labels = Vec::new();
+		let mut code = Vec::new();
+		for cell in cells {
+			match gas::parse(cell, self.typed) {
+				Line::Label(label) => labels.push((label, self.end + code.len() as i64)),
+				Line::Comment => break,
+				parsed => code.push((cell, parsed)),
+			}
+		}
+		let mut values = Vec::with_capacity(code.len());
+		for (cell, parsed) in code {
+			values.push(match parsed {
+				Line::Op(op) => Value::from(op),
+				Line::Ref(label) => match labels.iter().find(|&&(l, _)| l == label) {
+					Some(&(_, n)) => Value::I(n),
+					None => self.addr(cell)?,
+				},
+				Line::Typed(val) => val,
+				Line::Str(s) => Value::from(s),
+				_ => Value::from(cell),
+			});
+		}
+		for (label, n) in labels {
+			self.labels.insert(String::from(label), n);
+		}
+		if values.is_empty() {
+			return Ok(());
+		}
+		let start = self.end;
+		for val in values {
+			self.tape.tape.insert(Value::I(self.end), val);
+			self.end += 1;
+		}
+		self.tape.idx = Value::I(start);
+		self.tape.dir = true;
+		match self.tape.run(loader, policy) {
+			Ok(0) => Ok(()),
+			Ok(status) => Err(format!("exited with status {}", status)),
+			Err(e) => Err(e.to_string()),
+		}
+	}
+
+	pub fn command(
+		&mut self,
+		line: &str,
+		loader: &mut Loader,
+		policy: &mut Policy,
+	) -> Result<String, String> {
+		let words = words(line);
+		match &words[..] {
+			[".set", addr, val] => {
+				let addr = self.addr(addr)?;
+				self.tape.tape.insert(addr, Value::from(unquote(val)));
+				Ok(String::new())
+			}
+			[".call", name, args @ ..] => {
+				let name = Value::from(unquote(name));
+				if loader
+					.resolve(None, &name)
+					.map_err(|e| e.to_string())?
+					.is_none()
+				{
+					return Err(format!("unknown module: {}", name));
+				}
+				let mut child = Tape::new(None);
+				child.tape.insert(Value::I(0), Value::I(14));
+				child.tape.insert(Value::I(1), name);
+				child.tape.insert(Value::I(2), Value::I(OUT));
+				child.tape.insert(Value::I(3), Value::I(IN));
+				for (i, arg) in args.iter().enumerate() {
+					child
+						.tape
+						.insert(Value::I(IN + i as i64), Value::from(unquote(arg)));
+				}
+				child.op14(loader, policy).map_err(|e| e.to_string())?;
+				let mut out = Vec::new();
+				let mut idx = Value::I(OUT);
+				while let Some(val) = child.get(&idx) {
+					out.push(show(val));
+					idx.incr();
+				}
+				Ok(out.join(" "))
+			}
+			[".show", range @ ..] if range.len() <= 2 => {
+				let from = range.first().map(|a| self.addr(a)).transpose()?;
+				let to = range.get(1).map(|a| self.addr(a)).transpose()?;
+				let mut cells = self
+					.tape
+					.tape
+					.iter()
+					.filter(|&(addr, _)| match (&from, addr) {
+						(None, _) => true,
+						(Some(Value::I(from)), &Value::I(a)) => match to {
+							Some(Value::I(to)) => *from <= a && a <= to,
+							_ if range.len() == 1 => *from == a,
+							_ => false,
+						},
+						(Some(from), addr) => from == addr,
+					})
+					.collect::<Vec<_>>();
+				cells.sort_by_key(|&(addr, _)| order(addr));
+				let mut out = cells
+					.into_iter()
+					.map(|(addr, val)| format!("{}: {}", show(addr), show(val)))
+					.collect::<Vec<_>>();
+				out.push(format!(
+					"idx: {} dir: {}",
+					show(&self.tape.idx),
+					self.tape.dir
+				));
+				Ok(out.join("\n"))
+			}
+			[".help"] => Ok(String::from(HELP)),
+			_ => Err(format!("unknown command: {}", line)),
+		}
+	}
+}
+
+pub fn repl(loader: &mut Loader, policy: &mut Policy) {
+	let mut repl = Repl::new();
+	let stdin = io::stdin();
+	let mut line = String::new();
+	loop {
+		print!("oil> ");
+		io::stdout().flush().ok();
+		line.clear();
+		if stdin.lock().read_line(&mut line).unwrap_or(0) == 0 {
+			println!();
+			return;
+		}
+		let line = line.trim_end_matches(&['\r', '\n'][..]);
+		let result = if line == ".quit" {
+			return;
+		} else if line.starts_with('.') {
+			repl.command(line, loader, policy)
+		} else {
+			repl.append(line, loader, policy).map(|_| String::new())
+		};
+		policy.flush().ok();
+		match result {
+			Ok(ref out) if out.is_empty() => (),
+			Ok(out) => println!("{}", out),
+			Err(e) => println!("error: {}", e),
+		}
+	}
+}
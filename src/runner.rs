@@ -0,0 +1,183 @@
+use fxhash::FxHashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::error::Error;
+use crate::gas::{self, TestBlock};
+use crate::loader::{self, Loader};
+use crate::policy::{Input, Output, Policy};
+use crate::tape::Tape;
+use crate::value::{unquote, Value};
+
+pub enum Outcome {
+	Pass,
+	Fail(Vec<String>),
+	Error(String),
+	Skip,
+}
+
+fn is_program(path: &Path) -> bool {
+	match path.extension().and_then(|ext| ext.to_str()) {
+		Some("oil") | Some("gas") => path.is_file(),
+		_ => false,
+	}
+}
+
+pub fn discover(path: &Path) -> io::Result<Vec<PathBuf>> {
+	let mut found = Vec::new();
+	if path.is_dir() {
+		for ent in fs::read_dir(path)? {
+			let path = ent?.path();
+			if path.is_dir() {
+				found.extend(discover(&path)?);
+			} else if is_program(&path) {
+				found.push(path);
+			}
+		}
+	} else {
+		found.push(path.to_path_buf());
+	}
+	found.sort();
+	Ok(found)
+}
+
+pub struct Case {
+	pub name: String,
+	pub outcome: Outcome,
+}
+
+pub fn program(path: &Path) -> io::Result<(FxHashMap<Value, Value>, Vec<TestBlock>)> {
+	if path.extension().is_some_and(|ext| ext == "gas") {
+		let asm = gas::assemble(&fs::read_to_string(path)?);
+		Ok((asm.tape(), asm.tests))
+	} else {
+		Ok((loader::load(path)?, Vec::new()))
+	}
+}
+
+pub fn diff(expected: &str, actual: &str) -> Vec<String> {
+	let expected = expected.lines().collect::<Vec<_>>();
+	let actual = actual.lines().collect::<Vec<_>>();
+	let mut lines = Vec::new();
+	for i in 0..expected.len().max(actual.len()) {
+		match (expected.get(i), actual.get(i)) {
+			(Some(e), Some(a)) if e == a => (),
+			(e, a) => {
+				lines.push(format!("line {}:", i + 1));
+				if let Some(e) = e {
+					lines.push(format!("-{}", e));
+				}
+				if let Some(a) = a {
+					lines.push(format!("+{}", a));
+				}
+			}
+		}
+	}
+	if lines.is_empty() {
+		lines.push(String::from("trailing newline differs"));
+	}
+	lines
+}
+
+fn policy(template: &Policy, input: Vec<String>) -> Policy {
+	Policy {
+		input: match template.input {
+			Input::Deny => Input::Deny,
+			_ => Input::Buffer(input.into()),
+		},
+		eof: template.eof.clone(),
+		bytes: template.bytes,
+		output: match template.output {
+			Output::Deny => Output::Deny,
+			_ => Output::Capture(Vec::new()),
+		},
+		output_limit: template.output_limit,
+		rand: template.rand.clone(),
+		modules: template.modules.clone(),
+		step_limit: template.step_limit,
+		..Policy::default()
+	}
+}
+
+fn execute(
+	root: Option<&Path>,
+	cells: FxHashMap<Value, Value>,
+	input: Vec<String>,
+	loader: &mut Loader,
+	template: &Policy,
+) -> Result<(String, Tape), Error> {
+	let mut policy = policy(template, input);
+	let mut tape = Tape::new(root);
+	tape.tape = cells;
+	tape.run(loader, &mut policy)?;
+	match policy.output {
+		Output::Capture(actual) => Ok((String::from_utf8_lossy(&actual).into_owned(), tape)),
+		_ => Ok((String::new(), tape)),
+	}
+}
+
+fn run_block(
+	root: Option<&Path>,
+	cells: FxHashMap<Value, Value>,
+	block: TestBlock,
+	loader: &mut Loader,
+	template: &Policy,
+) -> Outcome {
+	let (actual, tape) = match execute(root, cells, block.input, loader, template) {
+		Ok(result) => result,
+		Err(e) => return Outcome::Error(e.to_string()),
+	};
+	let mut lines = Vec::new();
+	if !actual.lines().eq(block.output.iter().map(|line| &line[..])) {
+		lines.extend(diff(&block.output.join("\n"), &actual));
+	}
+	for (addr, val) in block.cells {
+		let got = tape.read_val(&Value::from(&addr[..]));
+		if got != unquote(&val).unwrap_or_else(|| Value::from(&val[..])) {
+			lines.push(format!("cell {}: expected {}, got {}", addr, val, got));
+		}
+	}
+	if lines.is_empty() {
+		Outcome::Pass
+	} else {
+		Outcome::Fail(lines)
+	}
+}
+
+pub fn run_file(path: &Path, loader: &mut Loader, template: &Policy) -> Vec<Case> {
+	let name = path.display().to_string();
+	let (cells, tests) = match program(path) {
+		Ok(program) => program,
+		Err(e) => {
+			return vec![Case {
+				name,
+				outcome: Outcome::Error(e.to_string()),
+			}]
+		}
+	};
+	let mut cases = Vec::new();
+	match fs::read_to_string(path.with_extension("out")) {
+		Ok(expected) => {
+			let input = fs::read_to_string(path.with_extension("in")).unwrap_or_default();
+			let input = input.lines().map(String::from).collect();
+			let outcome = match execute(path.parent(), cells.clone(), input, loader, template) {
+				Ok((ref actual, _)) if *actual == expected => Outcome::Pass,
+				Ok((actual, _)) => Outcome::Fail(diff(&expected, &actual)),
+				Err(e) => Outcome::Error(e.to_string()),
+			};
+			cases.push(Case { name, outcome });
+		}
+		Err(_) if tests.is_empty() => cases.push(Case {
+			name,
+			outcome: Outcome::Skip,
+		}),
+		Err(_) => (),
+	}
+	for block in tests {
+		let name = format!("{}::{}", path.display(), block.name);
+		let outcome = run_block(path.parent(), cells.clone(), block, loader, template);
+		cases.push(Case { name, outcome });
+	}
+	cases
+}
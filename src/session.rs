@@ -0,0 +1,69 @@
+use std::io::{self, Write};
+
+const MAGIC: &str = "oil session 1";
+
+pub struct Session {
+	pub seed: u64,
+	pub input: Vec<u8>,
+	pub output: Vec<u8>,
+}
+
+fn field<'a>(data: &mut &'a [u8], name: &str) -> Result<&'a str, String> {
+	let end = data
+		.iter()
+		.position(|&b| b == b'\n')
+		.ok_or_else(|| format!("missing {}", name))?;
+	let line = std::str::from_utf8(&data[..end]).map_err(|_| format!("bad {} line", name))?;
+	*data = &data[end + 1..];
+	match line.strip_prefix(name) {
+		Some(val) => Ok(val.trim_start()),
+		None => Err(format!("expected {}, found {}", name, line)),
+	}
+}
+
+fn block(data: &mut &[u8], name: &str) -> Result<Vec<u8>, String> {
+	let len = field(data, name)?
+		.parse::<usize>()
+		.map_err(|_| format!("bad {} length", name))?;
+	if data.len() <= len || data[len] != b'\n' {
+		return Err(format!("{} is truncated", name));
+	}
+	let bytes = data[..len].to_vec();
+	*data = &data[len + 1..];
+	Ok(bytes)
+}
+
+impl Session {
+	pub fn new(seed: u64) -> Session {
+		Session {
+			seed,
+			input: Vec::new(),
+			output: Vec::new(),
+		}
+	}
+
+	pub fn parse(mut data: &[u8]) -> Result<Session, String> {
+		field(&mut data, MAGIC)?;
+		let seed = field(&mut data, "seed")?
+			.parse()
+			.map_err(|_| String::from("bad seed"))?;
+		let input = block(&mut data, "input")?;
+		let output = block(&mut data, "output")?;
+		Ok(Session {
+			seed,
+			input,
+			output,
+		})
+	}
+
+	pub fn write(&self, w: &mut dyn Write) -> io::Result<()> {
+		writeln!(w, "{}", MAGIC)?;
+		writeln!(w, "seed {}", self.seed)?;
+		writeln!(w, "input {}", self.input.len())?;
+		w.write_all(&self.input)?;
+		writeln!(w)?;
+		writeln!(w, "output {}", self.output.len())?;
+		w.write_all(&self.output)?;
+		writeln!(w)
+	}
+}
@@ -1,55 +1,142 @@
-use super::stdlib::gen_libs;
+use super::error::Error;
+use super::loader::{Loader, Module};
+use super::policy::Policy;
 use super::value::{is_num, num_gtz, Value, ValueAsChars};
 use fxhash::FxHashMap;
 use rand::distributions::{uniform, Distribution};
-use rand::{thread_rng, Rng};
+use rand::Rng;
 use std::char;
 use std::cmp::{Ord, Ordering};
-use std::collections::hash_map::Entry;
-use std::fmt::Write;
-use std::fs;
-use std::i64;
-use std::io::{self, BufRead, BufReader};
+use std::fmt::{self, Write};
+use std::mem;
 use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
-pub struct Tape<'a> {
+pub type Image = Rc<FxHashMap<Value, Value>>;
+
+pub const ARGS: i64 = -1;
+pub const ENV: i64 = -1_000_000;
+
+pub struct Tape {
 	pub idx: Value,
 	pub tape: FxHashMap<Value, Value>,
+	pub image: Image,
 	pub dir: bool,
-	pub root: Option<&'a Path>,
+	pub root: Option<PathBuf>,
+	pub journal: Option<Vec<(Value, Option<Value>)>>,
+	pub watches: Vec<Watch>,
+	pub hits: Vec<Hit>,
+}
+
+pub struct Watch {
+	pub from: Value,
+	pub to: Value,
+	pub stop: bool,
+}
+
+#[derive(Clone)]
+pub struct Hit {
+	pub writer: Value,
+	pub addr: Value,
+	pub old: Option<Value>,
+	pub new: Option<Value>,
+	pub stop: bool,
+}
+
+impl fmt::Display for Hit {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "cell {} written at {}: ", self.addr, self.writer)?;
+		match self.old {
+			Some(ref old) => write!(f, "{}", old)?,
+			None => f.write_str("(empty)")?,
+		}
+		match self.new {
+			Some(ref new) => write!(f, " -> {}", new),
+			None => f.write_str(" -> (empty)"),
+		}
+	}
+}
+
+impl Watch {
+	pub fn contains(&self, addr: &Value) -> bool {
+		match (&self.from, &self.to, addr) {
+			(&Value::I(from), &Value::I(to), &Value::I(a)) => from <= a && a <= to,
+			(from, _, addr) => from == addr,
+		}
+	}
 }
 
-struct TapeChild<'a, 'b: 'a> {
-	pub tape: Tape<'a>,
-	pub parent: &'a mut Tape<'b>,
+pub struct Frame {
+	pub tape: Tape,
+	pub name: Value,
+	pub call: Value,
 	pub iidx: Value,
 	pub oidx: Value,
 }
 
-impl<'a> Tape<'a> {
-	pub fn new(root: Option<&'a Path>) -> Tape<'a> {
+impl Tape {
+	pub fn new(root: Option<&Path>) -> Tape {
 		Tape {
 			idx: Value::I(0),
 			dir: true,
 			tape: FxHashMap::default(),
-			root: root,
+			image: Image::default(),
+			root: root.map(Path::to_path_buf),
+			journal: None,
+			watches: Vec::new(),
+			hits: Vec::new(),
+		}
+	}
+	pub fn set_list(&mut self, base: i64, vals: &[String]) {
+		if vals.is_empty() {
+			return;
 		}
+		let mut idx = Value::I(base);
+		self.tape.insert(idx.clone(), Value::I(vals.len() as i64));
+		for val in vals {
+			idx.decr();
+			self.tape.insert(idx.clone(), Value::from(&val[..]));
+		}
+	}
+	pub fn get(&self, i: &Value) -> Option<&Value> {
+		self.tape.get(i).or_else(|| self.image.get(i))
+	}
+	pub fn get_mut(&mut self, i: &Value) -> Option<&mut Value> {
+		if !self.tape.contains_key(i) {
+			let x = self.image.get(i)?.clone();
+			self.log(i);
+			self.tape.insert(i.clone(), x);
+		} else {
+			self.log(i);
+		}
+		self.tape.get_mut(i)
+	}
+	fn log(&mut self, i: &Value) {
+		if self.journal.is_some() {
+			let old = self.get(i).cloned();
+			if let Some(ref mut journal) = self.journal {
+				journal.push((i.clone(), old));
+			}
+		}
+	}
+	fn set(&mut self, i: Value, val: Value) {
+		self.log(&i);
+		self.tape.insert(i, val);
 	}
 	pub fn step(&mut self) {
 		self.idx.advance(self.dir)
 	}
 	pub fn read_val(&self, i: &Value) -> Value {
-		if let Some(x) = self.tape.get(&i) {
+		if let Some(x) = self.get(i) {
 			x.clone()
 		} else {
 			Value::I(0)
 		}
 	}
 	pub fn read_int(&self) -> Value {
-		match self.tape.get(&self.idx) {
+		match self.get(&self.idx) {
 			Some(&Value::I(x)) => Value::I(x),
-			Some(&Value::S(ref s)) if is_num(&s[..]) => Value::S(s.clone()),
+			Some(Value::S(s)) if is_num(&s[..]) => Value::S(s.clone()),
 			_ => Value::I(0),
 		}
 	}
@@ -59,7 +146,7 @@ impl<'a> Tape<'a> {
 		let a = self.read_val(&a);
 		self.step();
 		let b = self.read_int();
-		self.tape.insert(b, a);
+		self.set(b, a);
 	}
 	pub fn op7(&mut self) {
 		self.step();
@@ -73,21 +160,19 @@ impl<'a> Tape<'a> {
 	pub fn op8(&mut self) {
 		self.step();
 		let a = self.read_int();
-		match self.tape.entry(a) {
-			Entry::Occupied(mut ent) => ent.get_mut().incr(),
-			Entry::Vacant(ent) => {
-				ent.insert(Value::I(1));
-			}
+		if let Some(x) = self.get_mut(&a) {
+			x.incr();
+		} else {
+			self.set(a, Value::I(1));
 		}
 	}
 	pub fn op9(&mut self) {
 		self.step();
 		let a = self.read_int();
-		match self.tape.entry(a) {
-			Entry::Occupied(mut ent) => ent.get_mut().decr(),
-			Entry::Vacant(ent) => {
-				ent.insert(Value::I(-1));
-			}
+		if let Some(x) = self.get_mut(&a) {
+			x.decr();
+		} else {
+			self.set(a, Value::I(-1));
 		}
 	}
 	pub fn op10(&mut self) {
@@ -110,10 +195,10 @@ impl<'a> Tape<'a> {
 		let (aiter, alen) = ValueAsChars::new(&a);
 		self.step();
 		let mut b = self.read_int();
-		self.tape.insert(b.clone(), Value::I(alen as i64));
+		self.set(b.clone(), Value::I(alen as i64));
 		for ch in aiter {
 			b.advance(self.dir);
-			self.tape.insert(b.clone(), Value::from(ch));
+			self.set(b.clone(), Value::from(ch));
 		}
 	}
 	pub fn op13(&mut self) {
@@ -129,141 +214,95 @@ impl<'a> Tape<'a> {
 					write!(s, "{}", self.read_val(&a)).ok();
 					a.advance(self.dir);
 				}
-				self.tape.insert(c, Value::from(s));
+				self.set(c, Value::from(s));
 			}
 			_ => self.step(),
 		}
 	}
 
-	fn mk_child<'b>(&'b mut self, path: Option<&'b Path>, oi: Value, ii: Value) -> TapeChild<'b, 'a>
-	where
-		'a: 'b,
-	{
-		TapeChild::<'b, 'a> {
-			tape: Tape::<'b>::new(path),
-			parent: self,
-			oidx: oi,
-			iidx: ii,
-		}
-	}
-
-	pub fn op14(
-		&mut self,
-		stdlib: &FxHashMap<&'static str, FxHashMap<Value, Value>>,
-		modcache: &mut FxHashMap<PathBuf, FxHashMap<Value, Value>>,
-	) {
+	fn callee(&mut self, loader: &mut Loader, policy: &mut Policy) -> Result<Option<Frame>, Error> {
+		let call = self.idx.clone();
 		self.step();
 		let pathidx = self.idx.clone();
 		self.step();
-		let oi = self.read_int();
+		let oidx = self.read_int();
 		self.step();
-		let ii = self.read_int();
-		let cachetape;
-		let path = {
-			let path = match self.read_val(&pathidx) {
-				Value::S(ref x) => {
-					let fpath = Path::new(&x[..]);
-					let (path, is_file) = if let Some(root) = self.root {
-						let path = root.join(fpath);
-						let is_file = path.is_file();
-						(path, is_file)
-					} else {
-						(PathBuf::new(), false)
-					};
-					if !is_file {
-						if let Some(lib) = stdlib.get(&x[..]).map(|m| m.clone()) {
-							let mut child = self.mk_child(None, oi, ii);
-							child.tape.tape = lib;
-							child.run(stdlib, modcache);
-						}
-						return;
-					}
-					path
-				}
-				Value::I(x) => {
-					let xs = x.to_string();
-					let fpath = Path::new(&xs);
-					if let Some(root) = self.root {
-						root.join(fpath)
-					} else {
-						return;
-					}
-				}
-				Value::C(x) => {
-					let mut buf = [0u8; 4];
-					let cx = x.encode_utf8(&mut buf);
-					let fpath = Path::new(cx);
-					if let Some(root) = self.root {
-						root.join(fpath)
-					} else {
-						return;
-					}
-				}
-			};
-			let mut child = self.mk_child(path.parent(), oi, ii);
-			if let Some(m) = modcache.get(&path).map(|m| m.clone()) {
-				child.tape.tape = m;
-				child.run(stdlib, modcache);
-				return;
-			} else if let Ok(f) = fs::File::open(&path) {
-				let mut f = BufReader::new(f);
-				let mut line = String::new();
-				let mut idx = 0;
-				while let Ok(n) = f.read_line(&mut line) {
-					if n == 0 {
-						break;
-					}
-					child
-						.tape
-						.tape
-						.insert(Value::I(idx), Value::from(line.trim_end_matches('\n')));
-					line.clear();
-					idx += 1;
-				}
-				cachetape = child.tape.tape.clone();
-				child.run(stdlib, modcache);
-				path.to_owned()
-			} else {
-				return;
-			}
+		let iidx = self.read_int();
+		let name = self.read_val(&pathidx);
+		policy.check_call(&call, &name)?;
+		let tape = match loader.resolve(self.root.as_deref(), &name)? {
+			Some(Module::Stdlib(image)) => Tape {
+				image,
+				..Tape::new(None)
+			},
+			Some(Module::File(path)) => Tape {
+				image: loader.image(&path)?,
+				..Tape::new(path.parent())
+			},
+			None => return Ok(None),
 		};
-		modcache.insert(path, cachetape);
+		Ok(Some(Frame {
+			tape,
+			name,
+			call,
+			iidx,
+			oidx,
+		}))
+	}
+
+	pub fn op14(&mut self, loader: &mut Loader, policy: &mut Policy) -> Result<(), Error> {
+		if let Some(mut frame) = self.callee(loader, policy)? {
+			frame.run(self, loader, policy)?;
+		}
+		Ok(())
+	}
+	pub fn enter(
+		&mut self,
+		loader: &mut Loader,
+		policy: &mut Policy,
+	) -> Result<Option<Frame>, Error> {
+		policy.tick(&self.idx)?;
+		let frame = self.callee(loader, policy)?;
+		self.step();
+		Ok(frame)
 	}
-	pub fn op15(&mut self) {
+	pub fn op15(&mut self, policy: &mut Policy) -> Result<(), Error> {
+		let rng = policy.rng(&self.idx)?;
 		self.step();
 		let a = self.read_int();
-		match self.tape.entry(a) {
-			Entry::Occupied(mut ent) => {
-				let val = ent.get_mut();
-				let mut rng = thread_rng();
-				match *val {
-					Value::I(ref mut x @ i64::MAX) => *x = rng.gen_range(0..=i64::MAX),
-					Value::I(ref mut x) => {
-						if *x > 0 {
-							*x = rng.gen_range(0..=*x)
-						}
-					}
-					Value::S(ref mut x) if num_gtz(x) => {
-						let range9 = uniform::Uniform::new_inclusive(b'0', b'9');
-						let s = Rc::make_mut(x);
-						let b = unsafe { s.as_mut_vec() };
-						let mut oldb = b.clone();
-						while {
-							for c in b.iter_mut() {
-								*c = range9.sample(&mut rng);
-							}
-							b.cmp(&&mut oldb) == Ordering::Greater
-						} {}
-						while b[0] == b'0' {
-							b.swap_remove(0);
-						}
+		let mut val = match self.get(&a) {
+			Some(val) => val.clone(),
+			None => return Ok(()),
+		};
+		match val {
+			Value::I(ref mut x @ i64::MAX) => *x = rng.gen_range(0..=i64::MAX),
+			Value::I(ref mut x) => {
+				if *x > 0 {
+					*x = rng.gen_range(0..=*x)
+				}
+			}
+			Value::S(ref mut x) if num_gtz(x) => {
+				let range9 = uniform::Uniform::new_inclusive(b'0', b'9');
+				let s = Rc::make_mut(x);
+				let b = unsafe { s.as_mut_vec() };
+				let mut oldb = b.clone();
+				while {
+					for c in b.iter_mut() {
+						*c = range9.sample(rng);
 					}
-					Value::S(ref x) if is_num(x) => (),
-					_ => *val = Value::I(0),
+					b.cmp(&&mut oldb) == Ordering::Greater
+				} {}
+				while b[0] == b'0' {
+					b.swap_remove(0);
 				}
 			}
-			Entry::Vacant(_) => (),
+			Value::S(ref x) if is_num(x) => (),
+			_ => val = Value::I(0),
+		}
+		if self.get(&a) != Some(&val) {
+			self.set(a, val);
 		}
+		Ok(())
 	}
 	pub fn op16(&mut self) {
 		self.step();
@@ -272,10 +311,10 @@ impl<'a> Tape<'a> {
 		let (aiter, alen) = ValueAsChars::new(&a);
 		self.step();
 		let mut b = self.read_int();
-		self.tape.insert(b.clone(), Value::I(alen as i64));
+		self.set(b.clone(), Value::I(alen as i64));
 		for ch in aiter {
 			b.advance(self.dir);
-			self.tape.insert(b.clone(), Value::I(ch as u32 as i64));
+			self.set(b.clone(), Value::I(ch as u32 as i64));
 		}
 	}
 	pub fn op17(&mut self) {
@@ -289,79 +328,134 @@ impl<'a> Tape<'a> {
 				let mut s = String::with_capacity(b as usize);
 				for _ in 0..b {
 					s.push(match self.read_val(&a) {
-						Value::I(x) if x >= 0 && x <= 0x10ffff => {
+						Value::I(x) if (0..=0x10ffff).contains(&x) => {
 							char::from_u32(x as u32).unwrap_or('\u{fffd}')
 						}
 						_ => '\u{fffd}',
 					});
 					a.advance(self.dir);
 				}
-				self.tape.insert(c, Value::from(s));
+				self.set(c, Value::from(s));
 			}
 			_ => self.step(),
 		}
 	}
-	pub fn run(&mut self) {
-		let mut modcache = FxHashMap::default();
-		let stdlib = gen_libs();
-		loop {
-			match self.tape.get(&self.idx) {
-				Some(&Value::I(cell)) => match cell {
-					1 => self.op1(),
-					2 => self.dir ^= true,
-					3 => return,
-					4 => {
-						self.step();
-						let a = self.read_int();
-						print!("{}", self.read_val(&a));
-					}
-					5 => {
-						(&mut io::stdout() as &mut dyn io::Write).flush().ok();
-						let stdin = io::stdin();
-						let mut inlock = stdin.lock();
-						let mut s = String::new();
-						inlock.read_line(&mut s).ok();
-						if s.ends_with('\n') {
-							let len = s.len() - 1;
-							s.truncate(len);
+	pub fn exec(&mut self, loader: &mut Loader, policy: &mut Policy) -> Result<bool, Error> {
+		if self.watches.is_empty() {
+			return self.op(loader, policy);
+		}
+		let writer = self.idx.clone();
+		let outer = self.journal.replace(Vec::new());
+		let result = self.op(loader, policy);
+		let writes = mem::replace(&mut self.journal, outer).unwrap_or_default();
+		for (addr, old) in &writes {
+			self.hit(&writer, addr, old);
+		}
+		if let Some(ref mut journal) = self.journal {
+			journal.extend(writes);
+		}
+		result
+	}
+	pub fn hit(&mut self, writer: &Value, addr: &Value, old: &Option<Value>) {
+		if let Some(watch) = self.watches.iter().find(|w| w.contains(addr)) {
+			self.hits.push(Hit {
+				writer: writer.clone(),
+				addr: addr.clone(),
+				old: old.clone(),
+				new: self.get(addr).cloned(),
+				stop: watch.stop,
+			});
+		}
+	}
+	fn op(&mut self, loader: &mut Loader, policy: &mut Policy) -> Result<bool, Error> {
+		policy.tick(&self.idx)?;
+		match self.get(&self.idx) {
+			Some(&Value::I(cell)) => match cell {
+				1 => self.op1(),
+				2 => self.dir ^= true,
+				3 => return Ok(false),
+				4 => {
+					let addr = self.idx.clone();
+					self.step();
+					let a = self.read_int();
+					match self.read_val(&a) {
+						Value::I(b @ 0..=255) if policy.bytes => {
+							policy.write_bytes(4, &addr, &[b as u8])?
 						}
-						self.step();
-						let a = self.read_int();
-						self.tape.insert(a, Value::from(s));
-					}
-					6 => {
-						self.step();
-						self.idx = self.read_int();
-						continue;
+						val => policy.write(4, &addr, format_args!("{}", val))?,
 					}
-					7 => {
-						self.op7();
-						continue;
-					}
-					8 => self.op8(),
-					9 => self.op9(),
-					10 => {
-						self.op10();
-						continue;
+				}
+				5 => {
+					let line = policy.read(&self.idx)?;
+					self.step();
+					let a = self.read_int();
+					match line {
+						Some(val) => {
+							self.set(a, val);
+						}
+						None => match policy.eof(Value::from("")) {
+							Some((val, flag)) => {
+								self.set(a, val);
+								if let Some(flag) = flag {
+									self.set(flag.clone(), Value::I(1));
+								}
+							}
+							None => return Ok(false),
+						},
 					}
-					11 => println!(""),
-					12 => self.op12(),
-					13 => self.op13(),
-					14 => self.op14(&stdlib, &mut modcache),
-					15 => self.op15(),
-					16 => self.op16(),
-					17 => self.op17(),
-					_ => (),
-				},
-				Some(_) => (),
-				_ => return,
+				}
+				6 => {
+					self.step();
+					self.idx = self.read_int();
+					return Ok(true);
+				}
+				7 => {
+					self.op7();
+					return Ok(true);
+				}
+				8 => self.op8(),
+				9 => self.op9(),
+				10 => {
+					self.op10();
+					return Ok(true);
+				}
+				11 => policy.write(11, &self.idx, format_args!("\n"))?,
+				12 => self.op12(),
+				13 => self.op13(),
+				14 => self.op14(loader, policy)?,
+				15 => self.op15(policy)?,
+				16 => self.op16(),
+				17 => self.op17(),
+				_ => (),
+			},
+			Some(_) => (),
+			_ => return Ok(false),
+		}
+		self.step();
+		Ok(true)
+	}
+	pub fn status(&self, policy: &Policy) -> i64 {
+		match policy.exit_cell.as_ref().map(|addr| self.read_val(addr)) {
+			Some(Value::I(status)) => status,
+			Some(_) => 1,
+			None => 0,
+		}
+	}
+	pub fn run(&mut self, loader: &mut Loader, policy: &mut Policy) -> Result<i64, Error> {
+		loop {
+			let seen = self.hits.len();
+			let running = self.exec(loader, policy)?;
+			if let Some(hit) = self.hits[seen..].iter().find(|hit| hit.stop) {
+				return Err(Error::Watch(hit.clone()));
+			}
+			if !running {
+				return Ok(self.status(policy));
 			}
-			self.step();
 		}
 	}
 }
 
-impl<'p, 'pl> TapeChild<'p, 'pl> {
+impl Frame {
 	pub fn step(&mut self) {
 		self.tape.step()
 	}
@@ -371,62 +465,68 @@ impl<'p, 'pl> TapeChild<'p, 'pl> {
 	pub fn read_int(&self) -> Value {
 		self.tape.read_int()
 	}
-	pub fn run(
+	pub fn exec(
 		&mut self,
-		stdlib: &FxHashMap<&'static str, FxHashMap<Value, Value>>,
-		modcache: &mut FxHashMap<PathBuf, FxHashMap<Value, Value>>,
-	) {
-		loop {
-			match self.tape.tape.get(&self.tape.idx) {
-				Some(&Value::I(cell)) => match cell {
-					1 => self.tape.op1(),
-					2 => self.tape.dir ^= true,
-					3 => return,
-					4 => {
-						self.step();
-						let a = self.read_int();
-						let a = self.read_val(&a);
-						self.parent.tape.insert(self.oidx.clone(), a);
-						self.oidx.advance(self.parent.dir);
-					}
-					5 => {
-						self.step();
-						let a = self.read_int();
-						let v = if let Some(x) = self.parent.tape.get(&self.iidx) {
-							x.clone()
-						} else {
-							Value::I(0)
-						};
-						self.tape.tape.insert(a, v);
-						self.iidx.advance(self.parent.dir);
-					}
-					6 => {
-						self.step();
-						self.tape.idx = self.read_int();
-						continue;
-					}
-					7 => {
-						self.tape.op7();
-						continue;
-					}
-					8 => self.tape.op8(),
-					9 => self.tape.op9(),
-					10 => {
-						self.tape.op10();
-						continue;
-					}
-					12 => self.tape.op12(),
-					13 => self.tape.op13(),
-					14 => self.tape.op14(stdlib, modcache),
-					15 => self.tape.op15(),
-					16 => self.tape.op16(),
-					17 => self.tape.op17(),
-					_ => (),
-				},
-				Some(_) => (),
-				_ => return,
-			}
-			self.step();
+		parent: &mut Tape,
+		loader: &mut Loader,
+		policy: &mut Policy,
+	) -> Result<bool, Error> {
+		policy.tick(&self.tape.idx)?;
+		match self.tape.get(&self.tape.idx) {
+			Some(&Value::I(cell)) => match cell {
+				1 => self.tape.op1(),
+				2 => self.tape.dir ^= true,
+				3 => return Ok(false),
+				4 => {
+					self.step();
+					let a = self.read_int();
+					let a = self.read_val(&a);
+					parent.set(self.oidx.clone(), a);
+					self.oidx.advance(parent.dir);
+				}
+				5 => {
+					self.step();
+					let a = self.read_int();
+					let x = parent.get(&self.iidx).cloned().unwrap_or(Value::I(0));
+					self.tape.tape.insert(a, x);
+					self.iidx.advance(parent.dir);
+				}
+				6 => {
+					self.step();
+					self.tape.idx = self.read_int();
+					return Ok(true);
+				}
+				7 => {
+					self.tape.op7();
+					return Ok(true);
+				}
+				8 => self.tape.op8(),
+				9 => self.tape.op9(),
+				10 => {
+					self.tape.op10();
+					return Ok(true);
+				}
+				12 => self.tape.op12(),
+				13 => self.tape.op13(),
+				14 => self.tape.op14(loader, policy)?,
+				15 => self.tape.op15(policy)?,
+				16 => self.tape.op16(),
+				17 => self.tape.op17(),
+				_ => (),
+			},
+			Some(_) => (),
+			_ => return Ok(false),
 		}
+		self.step();
+		Ok(true)
+	}
+	pub fn run(
+		&mut self,
+		parent: &mut Tape,
+		loader: &mut Loader,
+		policy: &mut Policy,
+	) -> Result<(), Error> {
+		while self.exec(parent, loader, policy)? {}
+		Ok(())
 	}
 }
@@ -1,11 +1,10 @@
 use std::cmp::{self, Ord, Ordering};
 use std::fmt::{self, Display};
-use std::i64;
 use std::mem::MaybeUninit;
 use std::rc::Rc;
 use std::str::Chars;
 
-#[derive(Clone, Eq, PartialEq, Hash)]
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
 pub enum Value {
 	S(Rc<String>),
 	I(i64),
@@ -20,7 +19,7 @@ pub enum ValueAsChars<'a> {
 }
 
 impl<'a> ValueAsChars<'a> {
-	pub fn new(val: &'a Value) -> (ValueAsChars, usize) {
+	pub fn new(val: &'a Value) -> (ValueAsChars<'a>, usize) {
 		match *val {
 			Value::I(i64::MIN) => (
 				ValueAsChars::I([
@@ -32,7 +31,7 @@ impl<'a> ValueAsChars<'a> {
 					MaybeUninit::new(b'7'),
 					MaybeUninit::new(b'7'),
 					MaybeUninit::new(b'4'),
-					MaybeUninit::new(b'7'),
+					MaybeUninit::new(b'5'),
 					MaybeUninit::new(b'8'),
 					MaybeUninit::new(b'6'),
 					MaybeUninit::new(b'3'),
@@ -240,7 +239,7 @@ fn unum_cmp(a: &[u8], b: &[u8]) -> Ordering {
 pub fn num_gtz(s: &str) -> bool {
 	let mut chs = s.bytes();
 	match chs.next() {
-		Some(b'1'..=b'9') => chs.all(|c| c >= b'0' && c <= b'9'),
+		Some(b'1'..=b'9') => chs.all(|c: u8| c.is_ascii_digit()),
 		_ => false,
 	}
 }
@@ -250,15 +249,79 @@ pub fn is_num(s: &str) -> bool {
 		let mut chs = s.bytes();
 		match chs.next() {
 			Some(b'-') => match chs.next() {
-				Some(b'1'..=b'9') => chs.all(|c| c >= b'0' && c <= b'9'),
+				Some(b'1'..=b'9') => chs.all(|c: u8| c.is_ascii_digit()),
 				_ => false,
 			},
-			Some(b'1'..=b'9') => chs.all(|c| c >= b'0' && c <= b'9'),
+			Some(b'1'..=b'9') => chs.all(|c: u8| c.is_ascii_digit()),
 			_ => false,
 		}
 	}
 }
 
+fn delimit(delim: char, text: &str) -> String {
+	let mut s = String::with_capacity(text.len() + 2);
+	s.push(delim);
+	for c in text.chars() {
+		match c {
+			'\\' => s.push_str("\\\\"),
+			'\n' => s.push_str("\\n"),
+			'\t' => s.push_str("\\t"),
+			c if c == delim => {
+				s.push('\\');
+				s.push(c);
+			}
+			c if c.is_control() => s.push_str(&format!("\\u{{{:x}}}", c as u32)),
+			c => s.push(c),
+		}
+	}
+	s.push(delim);
+	s
+}
+
+pub fn quote(val: &Value) -> String {
+	match *val {
+		Value::S(ref s) => delimit('"', s),
+		Value::I(x) => x.to_string(),
+		Value::C(c) => delimit('\'', c.encode_utf8(&mut [0; 4])),
+	}
+}
+
+pub fn unquote(s: &str) -> Option<Value> {
+	let delim = s.chars().next().filter(|&c| c == '"' || c == '\'')?;
+	let inner = s[1..].strip_suffix(delim)?;
+	let mut text = String::with_capacity(inner.len());
+	let mut chars = inner.chars();
+	while let Some(c) = chars.next() {
+		match c {
+			'\\' => text.push(match chars.next()? {
+				'n' => '\n',
+				't' => '\t',
+				'u' => {
+					let rest = chars.as_str().strip_prefix('{')?;
+					let end = rest.find('}')?;
+					let c = u32::from_str_radix(&rest[..end], 16)
+						.ok()
+						.and_then(char::from_u32)?;
+					chars = rest[end + 1..].chars();
+					c
+				}
+				c @ ('\\' | '"' | '\'') => c,
+				_ => return None,
+			}),
+			c if c == delim => return None,
+			c => text.push(c),
+		}
+	}
+	if delim == '"' {
+		return Some(Value::S(Rc::new(text)));
+	}
+	let mut chars = text.chars();
+	match (chars.next(), chars.next()) {
+		(Some(c), None) => Some(Value::from(c)),
+		_ => None,
+	}
+}
+
 fn i64_parse(s: &str) -> Option<i64> {
 	if s == "0" {
 		return Some(0);
@@ -303,6 +366,21 @@ fn i64_parse(s: &str) -> Option<i64> {
 	}
 }
 
+pub fn show(val: &Value) -> String {
+	match *val {
+		Value::S(ref s) => format!("{:?}", s),
+		Value::C(c) => format!("{:?}", c),
+		Value::I(x) => x.to_string(),
+	}
+}
+
+pub fn order(addr: &Value) -> (u8, i64, String) {
+	match *addr {
+		Value::I(a) => (0, a, String::new()),
+		_ => (1, 0, addr.to_string()),
+	}
+}
+
 impl Value {
 	pub fn advance(&mut self, direction: bool) {
 		if direction {
@@ -313,72 +391,72 @@ impl Value {
 	}
 
 	pub fn incr(&mut self) {
-		let newx;
-		loop {
-			match *self {
-				Value::I(i64::MAX) => {
-					*self = Value::S(Rc::new(String::from("9223372036854775808")))
-				}
-				Value::I(ref mut x) => *x += 1,
-				Value::S(ref mut x) => {
-					if is_num(&x[..]) {
-						let s = Rc::make_mut(x);
-						unsafe {
-							let s = s.as_mut_vec();
-							if s[0] == b'-' {
-								num_decr_core(s, 1)
-							} else {
-								num_incr_core(s, 0)
-							}
-						}
-						if let Some(x) = i64_parse(s) {
-							newx = x;
-							break;
+		let newx = match *self {
+			Value::I(i64::MAX) => {
+				*self = Value::S(Rc::new(String::from("9223372036854775808")));
+				return;
+			}
+			Value::I(ref mut x) => {
+				*x += 1;
+				return;
+			}
+			Value::S(ref mut x) => {
+				if is_num(&x[..]) {
+					let s = Rc::make_mut(x);
+					unsafe {
+						let s = s.as_mut_vec();
+						if s[0] == b'-' {
+							num_decr_core(s, 1)
+						} else {
+							num_incr_core(s, 0)
 						}
+					}
+					if let Some(x) = i64_parse(s) {
+						x
 					} else {
-						newx = 1;
-						break;
+						return;
 					}
+				} else {
+					1
 				}
-				Value::C(_) => *self = Value::I(1),
 			}
-			return;
-		}
+			Value::C(_) => 1,
+		};
 		*self = Value::I(newx);
 	}
 
 	pub fn decr(&mut self) {
-		let newx;
-		loop {
-			match *self {
-				Value::I(i64::MIN) => {
-					*self = Value::S(Rc::new(String::from("-9223372036854775809")))
-				}
-				Value::I(ref mut x) => *x -= 1,
-				Value::S(ref mut x) => {
-					if is_num(&x[..]) {
-						let s = Rc::make_mut(x);
-						unsafe {
-							let s = s.as_mut_vec();
-							if s[0] == b'-' {
-								num_incr_core(s, 1)
-							} else {
-								num_decr_core(s, 0)
-							}
-						}
-						if let Some(x) = i64_parse(s) {
-							newx = x;
-							break;
+		let newx = match *self {
+			Value::I(i64::MIN) => {
+				*self = Value::S(Rc::new(String::from("-9223372036854775809")));
+				return;
+			}
+			Value::I(ref mut x) => {
+				*x -= 1;
+				return;
+			}
+			Value::S(ref mut x) => {
+				if is_num(&x[..]) {
+					let s = Rc::make_mut(x);
+					unsafe {
+						let s = s.as_mut_vec();
+						if s[0] == b'-' {
+							num_incr_core(s, 1)
+						} else {
+							num_decr_core(s, 0)
 						}
+					}
+					if let Some(x) = i64_parse(s) {
+						x
 					} else {
-						newx = -1;
-						break;
+						return;
 					}
+				} else {
+					-1
 				}
-				Value::C(_) => *self = Value::I(-1),
 			}
-			return;
-		}
+			Value::C(_) => -1,
+		};
 		*self = Value::I(newx);
 	}
 
@@ -389,9 +467,9 @@ impl Value {
 					return Value::I(z);
 				}
 			}
-			(_, &Value::S(ref s)) if !is_num(s) => return self.clone(),
+			(_, Value::S(s)) if !is_num(s) => return self.clone(),
 			(_, &Value::I(0)) | (_, &Value::C(_)) => return self.clone(),
-			(&Value::S(ref s), _) if !is_num(s) => return rhs.clone(),
+			(Value::S(s), _) if !is_num(s) => return rhs.clone(),
 			(&Value::I(0), _) | (&Value::C(_), _) => return rhs.clone(),
 			_ => (),
 		}
@@ -428,9 +506,9 @@ impl Value {
 					return Value::I(z);
 				}
 			}
-			(_, &Value::S(ref s)) if !is_num(s) => return self.clone(),
+			(_, Value::S(s)) if !is_num(s) => return self.clone(),
 			(_, &Value::I(0)) | (_, &Value::C(_)) => return self.clone(),
-			(&Value::S(ref s), _) if !is_num(s) => return rhs.as_negative_unchecked(),
+			(Value::S(s), _) if !is_num(s) => return rhs.as_negative_unchecked(),
 			(&Value::I(0), _) | (&Value::C(_), _) => return rhs.as_negative_unchecked(),
 			_ => (),
 		}
@@ -483,7 +561,7 @@ impl Value {
 
 impl<'a> From<&'a str> for Value {
 	fn from(s: &'a str) -> Value {
-		if let Some(x) = i64_parse(&s) {
+		if let Some(x) = i64_parse(s) {
 			Value::I(x)
 		} else {
 			if !s.is_empty() && s.chars().nth(1).is_none() {
@@ -0,0 +1,175 @@
+mod common;
+
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+
+fn oilrs_with(dir: &Path, args: &[&str], input: &str) -> Output {
+	let mut child = Command::new(env!("CARGO_BIN_EXE_oil-lang"))
+		.args(args)
+		.current_dir(dir)
+		.env_remove("OIL_PATH")
+		.stdin(Stdio::piped())
+		.stdout(Stdio::piped())
+		.stderr(Stdio::piped())
+		.spawn()
+		.unwrap();
+	child
+		.stdin
+		.take()
+		.unwrap()
+		.write_all(input.as_bytes())
+		.unwrap();
+	child.wait_with_output().unwrap()
+}
+
+fn oilrs(dir: &Path, args: &[&str]) -> Output {
+	oilrs_with(dir, args, "")
+}
+
+#[test]
+fn which() {
+	let dir = common::temp_dir("which");
+	fs::create_dir_all(dir.join("inc")).unwrap();
+	fs::write(dir.join("inc").join("m"), "3\n").unwrap();
+	let out = oilrs(&dir, &["which", "-I", "inc", "m"]);
+	assert!(out.status.success());
+	assert_eq!(
+		String::from_utf8_lossy(&out.stdout).trim(),
+		Path::new("inc").join("m").to_string_lossy()
+	);
+	fs::write(dir.join("m"), "3\n").unwrap();
+	let out = oilrs(&dir, &["which", "-I", "inc", "m"]);
+	assert_eq!(String::from_utf8_lossy(&out.stdout).trim(), "m");
+	let out = oilrs(&dir, &["which", "missing"]);
+	assert_eq!(out.status.code(), Some(1));
+	fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn repl_sandbox() {
+	let dir = common::temp_dir("repl-sandbox");
+	fs::create_dir_all(dir.join("root")).unwrap();
+	fs::write(dir.join("evil"), "3\n").unwrap();
+	let out = oilrs_with(&dir.join("root"), &["repl", "--sandbox"], ".call ../evil\n");
+	assert!(String::from_utf8_lossy(&out.stdout).contains("path traverses out of its root"));
+	fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn policy_flags() {
+	let dir = common::temp_dir("policy");
+	fs::write(dir.join("hi.oil"), "4\n3\n3\nhello\n").unwrap();
+	let out = oilrs(&dir, &["run", "--deny", "output", "hi.oil"]);
+	assert_eq!(out.status.code(), Some(1));
+	assert!(String::from_utf8_lossy(&out.stderr).contains("output (opcode 4) denied at 0"));
+	let out = oilrs(&dir, &["run", "--max-output", "2", "hi.oil"]);
+	assert_eq!(out.status.code(), Some(1));
+	assert!(String::from_utf8_lossy(&out.stderr).contains("exceeded the output limit of 2"));
+	fs::write(dir.join("call.oil"), "14\nm\n20\n30\n3\n").unwrap();
+	let out = oilrs(&dir, &["run", "--allow-call", "echo", "call.oil"]);
+	assert!(String::from_utf8_lossy(&out.stderr).contains("module m is not allowed"));
+	assert!(oilrs(&dir, &["run", "--allow-call", "m", "call.oil"])
+		.status
+		.success());
+	fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_honours_policy() {
+	let dir = common::temp_dir("test-policy");
+	fs::create_dir_all(dir.join("proj")).unwrap();
+	fs::write(dir.join("evil"), "3\n").unwrap();
+	fs::write(dir.join("proj/call.oil"), "14\n../evil\n20\n20\n3\n").unwrap();
+	fs::write(dir.join("proj/call.out"), "").unwrap();
+	fs::write(dir.join("proj/hi.oil"), "4\n3\n3\nhi\n").unwrap();
+	fs::write(dir.join("proj/hi.out"), "hi").unwrap();
+	let proj = dir.join("proj");
+	assert!(oilrs(&proj, &["test", "."]).status.success());
+	let out = oilrs(&proj, &["test", "--sandbox", "call.oil"]);
+	assert_eq!(out.status.code(), Some(1));
+	assert!(String::from_utf8_lossy(&out.stdout).contains("path traverses out of its root"));
+	let out = oilrs(&proj, &["test", "--deny", "output", "hi.oil"]);
+	assert_eq!(out.status.code(), Some(1));
+	assert!(String::from_utf8_lossy(&out.stdout).contains("output (opcode 4) denied"));
+	fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn exit_status_is_clamped() {
+	let dir = common::temp_dir("exit");
+	for &(val, code) in &[
+		("7", 7),
+		("256", 255),
+		("4294967296", 255),
+		("-1", 1),
+		("0", 0),
+	] {
+		fs::write(dir.join("exit.oil"), format!("3\n{}\n", val)).unwrap();
+		let out = oilrs(&dir, &["run", "--exit-cell", "1", "exit.oil"]);
+		assert_eq!(out.status.code(), Some(code), "exit cell {}", val);
+	}
+	fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn sparse_images() {
+	let dir = common::temp_dir("sparse");
+	fs::write(dir.join("far.gas"), ".org 1099511627776\n:far\nquit\n").unwrap();
+	let out = oilrs(&dir, &["asm", "far.gas"]);
+	assert_eq!(out.status.code(), Some(1));
+	let out = oilrs(&dir, &["asm", "--image", "far.gas"]);
+	assert!(out.status.success());
+	let out = oilrs(&dir, &["disasm", "far.oil"]);
+	assert!(out.status.success());
+	assert_eq!(
+		String::from_utf8_lossy(&out.stdout),
+		".org 1099511627776\n:far\nquit\n"
+	);
+	fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn legacy_assemble() {
+	let dir = common::temp_dir("legacy-asm");
+	fs::write(dir.join("prog.gas"), "write\n$x\nquit\n:x\nhi\n").unwrap();
+	let out = oilrs(&dir, &["prog.gas", "out.oil"]);
+	assert!(out.status.success());
+	assert!(out.stdout.is_empty());
+	assert_eq!(
+		fs::read_to_string(dir.join("out.oil")).unwrap(),
+		"4\n3\n3\nhi\n"
+	);
+	let out = oilrs(&dir, &["prog.gas"]);
+	assert_eq!(String::from_utf8_lossy(&out.stdout), "hi");
+	fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn env_names_and_values() {
+	let dir = common::temp_dir("env");
+	fs::write(
+		dir.join("env.oil"),
+		"4\n-1000000\n11\n4\n-1000001\n11\n4\n-1000002\n3\n",
+	)
+	.unwrap();
+	env::set_var("OIL_CLI_TEST_SET", "x y");
+	let out = oilrs(
+		&dir,
+		&[
+			"run",
+			"--env",
+			"OIL_CLI_TEST_SET",
+			"--env",
+			"OIL_CLI_TEST_UNSET",
+			"env.oil",
+		],
+	);
+	assert_eq!(
+		String::from_utf8_lossy(&out.stdout),
+		"2\nOIL_CLI_TEST_SET=x y\nOIL_CLI_TEST_UNSET"
+	);
+	fs::remove_dir_all(&dir).ok();
+}
@@ -0,0 +1,42 @@
+#![allow(dead_code)]
+
+use std::env;
+use std::fs;
+use std::io::Cursor;
+use std::path::PathBuf;
+
+use oil_lang::json::Json;
+use oil_lang::tape::Tape;
+use oil_lang::value::Value;
+
+pub fn tape(cells: &[&str]) -> Tape {
+	let mut tape = Tape::new(None);
+	for (idx, cell) in cells.iter().enumerate() {
+		tape.tape.insert(Value::I(idx as i64), Value::from(*cell));
+	}
+	tape
+}
+
+pub fn temp_dir(name: &str) -> PathBuf {
+	let dir = env::temp_dir().join(format!("oil-{}-{}", name, std::process::id()));
+	fs::remove_dir_all(&dir).ok();
+	fs::create_dir_all(&dir).unwrap();
+	dir
+}
+
+pub fn frame(messages: &[String]) -> Cursor<Vec<u8>> {
+	let mut input = Vec::new();
+	for msg in messages {
+		input.extend(format!("Content-Length: {}\r\n\r\n{}", msg.len(), msg).bytes());
+	}
+	Cursor::new(input)
+}
+
+pub fn unframe(output: Vec<u8>) -> Vec<Json> {
+	String::from_utf8(output)
+		.unwrap()
+		.split("Content-Length: ")
+		.skip(1)
+		.map(|msg| Json::parse(msg.split_once("\r\n\r\n").unwrap().1).unwrap())
+		.collect()
+}
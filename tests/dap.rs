@@ -0,0 +1,133 @@
+mod common;
+
+use oil_lang::dap;
+use oil_lang::json::Json;
+use oil_lang::loader::Loader;
+use oil_lang::policy::Policy;
+use std::fs;
+
+fn session(requests: &[&str]) -> Vec<Json> {
+	let requests = requests
+		.iter()
+		.enumerate()
+		.map(|(seq, req)| {
+			req.replacen(
+				'{',
+				&format!("{{\"seq\":{},\"type\":\"request\",", seq + 1),
+				1,
+			)
+		})
+		.collect::<Vec<_>>();
+	let mut output = Vec::new();
+	dap::serve(
+		&mut common::frame(&requests),
+		&mut output,
+		&mut Loader::new(),
+		&mut Policy::default(),
+	)
+	.unwrap();
+	common::unframe(output)
+}
+
+fn find<'a>(msgs: &'a [Json], key: &str, name: &str) -> Vec<&'a Json> {
+	msgs.iter()
+		.filter(|m| m.get(key).as_str() == Some(name))
+		.collect()
+}
+
+#[test]
+fn launch_break_step_into_call() {
+	let dir = common::temp_dir("dap");
+	fs::write(dir.join("echo"), "5\n5\n4\n5\n3\n").unwrap();
+	let path = dir.join("main.gas");
+	fs::write(
+		&path,
+		"call\necho\n$out\n$in\n# print what echo gave back\nwrite\n$out\nreturn\n:in\nhi\n:out\n",
+	)
+	.unwrap();
+	let program = path.to_str().unwrap().replace('\\', "\\\\");
+	let msgs = session(&[
+		r#"{"command":"initialize","arguments":{}}"#,
+		&format!(
+			r#"{{"command":"launch","arguments":{{"program":"{}","stopOnEntry":true}}}}"#,
+			program
+		),
+		&format!(
+			r#"{{"command":"setBreakpoints","arguments":{{"source":{{"path":"{}"}},"breakpoints":[{{"line":5}},{{"line":7}}]}}}}"#,
+			program
+		),
+		r#"{"command":"configurationDone"}"#,
+		r#"{"command":"stepIn","arguments":{"threadId":1}}"#,
+		r#"{"command":"stackTrace","arguments":{"threadId":1}}"#,
+		r#"{"command":"variables","arguments":{"variablesReference":3}}"#,
+		r#"{"command":"stepOut","arguments":{"threadId":1}}"#,
+		r#"{"command":"stackTrace","arguments":{"threadId":1}}"#,
+		r#"{"command":"continue","arguments":{"threadId":1}}"#,
+		r#"{"command":"disconnect"}"#,
+	]);
+	fs::remove_dir_all(&dir).ok();
+	let responses = find(&msgs, "type", "response");
+	assert!(responses
+		.iter()
+		.all(|r| r.get("success") == &Json::Bool(true)));
+	let lines = responses[2].get("body").get("breakpoints").as_array();
+	assert_eq!(lines[0].get("line").as_i64(), Some(6));
+	assert_eq!(lines[1].get("line").as_i64(), Some(6));
+	let stops = find(&msgs, "event", "stopped")
+		.iter()
+		.map(|e| e.get("body").get("reason").as_str().unwrap())
+		.collect::<Vec<_>>();
+	assert_eq!(stops, ["entry", "step", "step"]);
+	let inner = responses[5].get("body").get("stackFrames").as_array();
+	assert_eq!(inner.len(), 2);
+	assert_eq!(inner[0].get("name").as_str(), Some("echo at 0: read 5"));
+	assert_eq!(inner[1].get("line").as_i64(), Some(1));
+	let registers = responses[6].get("body").get("variables").as_array();
+	assert_eq!(registers[0].get("value").as_str(), Some("0"));
+	let outer = responses[8].get("body").get("stackFrames").as_array();
+	assert_eq!(outer.len(), 1);
+	assert_eq!(outer[0].get("line").as_i64(), Some(6));
+	let output = find(&msgs, "event", "output");
+	assert_eq!(output[0].get("body").get("output").as_str(), Some("hi"));
+	let exited = find(&msgs, "event", "exited");
+	assert_eq!(exited[0].get("body").get("exitCode").as_i64(), Some(0));
+}
+
+#[test]
+fn breakpoints_in_sparse_programs() {
+	let dir = common::temp_dir("dap-sparse");
+	let path = dir.join("main.gas");
+	fs::write(
+		&path,
+		"jump\n$far\n.org 1099511627776\n:far\nwrite\n$msg\nreturn\n:msg\nhi\n",
+	)
+	.unwrap();
+	let program = path.to_str().unwrap().replace('\\', "\\\\");
+	let msgs = session(&[
+		r#"{"command":"initialize","arguments":{}}"#,
+		&format!(
+			r#"{{"command":"launch","arguments":{{"program":"{}"}}}}"#,
+			program
+		),
+		&format!(
+			r#"{{"command":"setBreakpoints","arguments":{{"source":{{"path":"{}"}},"breakpoints":[{{"line":3}},{{"line":6}}]}}}}"#,
+			program
+		),
+		r#"{"command":"configurationDone"}"#,
+		r#"{"command":"stackTrace","arguments":{"threadId":1}}"#,
+		r#"{"command":"continue","arguments":{"threadId":1}}"#,
+		r#"{"command":"disconnect"}"#,
+	]);
+	fs::remove_dir_all(&dir).ok();
+	let responses = find(&msgs, "type", "response");
+	assert!(responses
+		.iter()
+		.all(|r| r.get("success") == &Json::Bool(true)));
+	let lines = responses[2].get("body").get("breakpoints").as_array();
+	assert_eq!(lines[0].get("line").as_i64(), Some(5));
+	assert_eq!(lines[1].get("line").as_i64(), Some(5));
+	let frames = responses[4].get("body").get("stackFrames").as_array();
+	assert_eq!(frames[0].get("line").as_i64(), Some(5));
+	let output = find(&msgs, "event", "output");
+	assert_eq!(output[0].get("body").get("output").as_str(), Some("hi"));
+}
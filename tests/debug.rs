@@ -0,0 +1,108 @@
+mod common;
+
+use oil_lang::debug::Debugger;
+use oil_lang::error::Error;
+use oil_lang::loader::Loader;
+use oil_lang::policy::{Input, Output, Policy};
+use oil_lang::tape::Watch;
+use oil_lang::value::Value;
+
+#[test]
+fn step_back_and_replay() {
+	let mut loader = Loader::new();
+	let mut policy = Policy {
+		input: Input::Buffer(vec![String::from("5")].into()),
+		output: Output::Capture(Vec::new()),
+		..Policy::default()
+	};
+	let mut dbg = Debugger::new(common::tape(&["5", "10", "8", "10", "4", "10", "3"]));
+	let mut run = |dbg: &mut Debugger, cmd: &str| dbg.command(cmd, &mut loader, &mut policy);
+	assert_eq!(run(&mut dbg, "step 3").unwrap(), "6: quit");
+	assert_eq!(dbg.tape.read_val(&Value::I(10)), Value::I(6));
+	assert_eq!(run(&mut dbg, "back write 10").unwrap(), "2: increment 10");
+	assert_eq!(dbg.tape.read_val(&Value::I(10)), Value::I(5));
+	assert_eq!(run(&mut dbg, "back").unwrap(), "0: read 10");
+	assert_eq!(dbg.tape.get(&Value::I(10)), None);
+	assert!(run(&mut dbg, "back").is_err());
+	assert_eq!(run(&mut dbg, "continue").unwrap(), "6: quit");
+	assert!(dbg.halted);
+	assert_eq!(dbg.tape.read_val(&Value::I(10)), Value::I(6));
+	assert!(run(&mut dbg, "back write 11").is_err());
+	match policy.output {
+		Output::Capture(ref out) => assert_eq!(out, b"6"),
+		_ => unreachable!(),
+	}
+}
+
+#[test]
+fn watchpoints() {
+	let mut loader = Loader::new();
+	let mut policy = Policy::default();
+	let mut tape = common::tape(&["8", "20", "1", "20", "21", "9", "30", "8", "20"]);
+	tape.watches.push(Watch {
+		from: Value::I(21),
+		to: Value::I(30),
+		stop: true,
+	});
+	tape.watches.push(Watch {
+		from: Value::I(20),
+		to: Value::I(20),
+		stop: false,
+	});
+	match tape.run(&mut loader, &mut policy) {
+		Err(Error::Watch(hit)) => {
+			assert_eq!(hit.writer, Value::I(2));
+			assert_eq!(hit.addr, Value::I(21));
+			assert_eq!(hit.old, None);
+			assert_eq!(hit.new, Some(Value::I(1)));
+		}
+		_ => panic!("expected the watchpoint on 21 to stop the run"),
+	}
+	assert_eq!(tape.hits.len(), 2);
+	match tape.run(&mut loader, &mut policy) {
+		Err(Error::Watch(hit)) => {
+			assert_eq!(hit.to_string(), "cell 30 written at 5: (empty) -> -1");
+		}
+		_ => panic!("expected the watchpoint on 30 to stop the run"),
+	}
+	assert!(tape.run(&mut loader, &mut policy).is_ok());
+	assert_eq!(tape.hits.len(), 4);
+	assert_eq!(tape.hits[3].new, Some(Value::I(2)));
+
+	tape.idx = Value::I(0);
+	tape.watches.clear();
+	let mut dbg = Debugger::new(tape);
+	let mut run = |dbg: &mut Debugger, cmd: &str| dbg.command(cmd, &mut loader, &mut policy);
+	assert_eq!(run(&mut dbg, "watch 30").unwrap(), "watching 30");
+	assert_eq!(
+		run(&mut dbg, "continue").unwrap(),
+		"cell 30 written at 5: -1 -> -2\n7: increment 20"
+	);
+	assert_eq!(run(&mut dbg, "back").unwrap(), "5: decrement 30");
+	assert_eq!(
+		run(&mut dbg, "step").unwrap(),
+		"cell 30 written at 5: -1 -> -2\n7: increment 20"
+	);
+	assert_eq!(dbg.tape.hits.len(), 6);
+}
+
+#[test]
+fn unchanged_rand_is_not_a_write() {
+	let mut loader = Loader::new();
+	let mut policy = Policy::default();
+	policy.seed(1);
+	let mut tape = common::tape(&[
+		"15", "10", "15", "11", "15", "12", "3", "", "", "", "0", "-4", "x",
+	]);
+	tape.watches.push(Watch {
+		from: Value::I(10),
+		to: Value::I(12),
+		stop: false,
+	});
+	tape.journal = Some(Vec::new());
+	assert!(matches!(tape.run(&mut loader, &mut policy), Ok(0)));
+	assert_eq!(tape.hits.len(), 1);
+	assert_eq!(tape.hits[0].addr, Value::I(12));
+	assert_eq!(tape.hits[0].new, Some(Value::I(0)));
+	assert_eq!(tape.journal.map(|j| j.len()), Some(1));
+}
@@ -0,0 +1,222 @@
+use fxhash::FxHashMap;
+use std::fs;
+use std::rc::Rc;
+
+use oil_lang::gas;
+use oil_lang::loader;
+use oil_lang::value::Value;
+
+#[test]
+fn disassemble_round_trips() {
+	for ent in fs::read_dir("gas").unwrap() {
+		let path = ent.unwrap().path();
+		let asm = gas::assemble(&fs::read_to_string(&path).unwrap());
+		let (lines, warnings) = gas::disassemble(&asm.tape(), &asm.labels);
+		assert!(warnings.is_empty(), "{}: {:?}", path.display(), warnings);
+		let again = gas::assemble(&(lines.join("\n") + "\n"));
+		assert_eq!(asm.cells, again.cells, "{}", path.display());
+		assert_eq!(asm.labels, again.labels, "{}", path.display());
+	}
+}
+
+#[test]
+fn disassemble_quotes_literals() {
+	let mut tape = FxHashMap::default();
+	tape.insert(Value::I(0), Value::I(4));
+	tape.insert(Value::I(1), Value::I(3));
+	tape.insert(Value::I(2), Value::from("jump"));
+	tape.insert(Value::I(3), Value::from("$x"));
+	tape.insert(Value::I(5), Value::I(99));
+	tape.insert(Value::I(4), Value::from("'5'"));
+	let (lines, _) = gas::disassemble(&tape, &[]);
+	assert_eq!(lines, ["output", "3", "\"jump", "\"$x", "\"'5'", "99"]);
+	tape.insert(Value::I(4), Value::S(Rc::new(String::from("5"))));
+	let (lines, _) = gas::disassemble(&tape, &[]);
+	assert_eq!(
+		lines,
+		[
+			gas::TYPED,
+			"output",
+			"3",
+			"\"jump\"",
+			"\"$x\"",
+			"\"5\"",
+			"99"
+		]
+	);
+}
+
+#[test]
+fn typed_literals() {
+	let src = "\"5\"\n\"007\n'x'\n\"a\\nb \\\"c\\\"\"\n\"5\n5\n\"x\n";
+	let legacy = gas::assemble(src);
+	assert_eq!(legacy.cells[0], Value::from("5\""));
+	assert_eq!(legacy.cells[2], Value::from("'x'"));
+	let asm = gas::assemble(&format!("{}\n{}", gas::TYPED, src));
+	assert_eq!(
+		asm.cells,
+		[
+			Value::S(Rc::new(String::from("5"))),
+			Value::from("007"),
+			Value::C('x'),
+			Value::S(Rc::new(String::from("a\nb \"c\""))),
+			Value::I(5),
+			Value::I(5),
+			Value::C('x'),
+		]
+	);
+	let (lines, warnings) = gas::disassemble(&asm.tape(), &[]);
+	assert!(warnings.is_empty());
+	assert_eq!(gas::assemble(&lines.join("\n")).cells, asm.cells);
+	let text = loader::encode(&asm.cells);
+	assert!(text.starts_with(loader::TYPED));
+	assert_eq!(loader::parse(&text), asm.tape());
+	let plain = gas::assemble("read\n7\n\"abc\n");
+	assert_eq!(loader::encode(&plain.cells), "5\n7\nabc\n");
+}
+
+#[test]
+fn heredoc_and_escapes() {
+	let src = "#gas typed\noutput\n\t\"\"\"\n# not a comment\n  jump $x\n\t\"\"\"\n\"a\\tb\\u{263a}\\u{1}\"\n";
+	let asm = gas::assemble(src);
+	assert!(asm.warnings.is_empty());
+	assert_eq!(
+		asm.cells,
+		[
+			Value::I(4),
+			Value::S(Rc::new(String::from("# not a comment\n  jump $x"))),
+			Value::S(Rc::new(String::from("a\tb\u{263a}\u{1}"))),
+		]
+	);
+	assert_eq!(asm.lines, [2, 3, 7]);
+	let (lines, _) = gas::disassemble(&asm.tape(), &[]);
+	assert_eq!(lines[3], "\"a\\tb\u{263a}\\u{1}\"");
+	assert_eq!(gas::assemble(&lines.join("\n")).cells, asm.cells);
+	assert_eq!(gas::format(src, gas::Spelling::Canonical), src);
+	assert_eq!(gas::assemble("#gas typed\n\"\"\"\nx\n").warnings.len(), 1);
+	assert_eq!(gas::assemble("\"\"\"\nx\n").cells.len(), 2);
+}
+
+#[test]
+fn test_blocks() {
+	let src = "read\n$x\nquit\n:x\n\
+		.test first\n.in a\n.in b c\n.out a\n.expect_cell $x a\n.expect_cell 9 \"q r\"\n.end\n\
+		.in stray\n\
+		.test open\n.expect_cell $nowhere 1\n";
+	let asm = gas::assemble(src);
+	assert_eq!(asm.cells, [Value::I(5), Value::I(3), Value::I(3)]);
+	assert_eq!(asm.tests.len(), 2);
+	let first = &asm.tests[0];
+	assert_eq!(first.name, "first");
+	assert_eq!(first.input, ["a", "b c"]);
+	assert_eq!(first.output, ["a"]);
+	assert_eq!(
+		first.cells,
+		[
+			(String::from("3"), String::from("a")),
+			(String::from("9"), String::from("\"q r\"")),
+		]
+	);
+	assert_eq!(asm.tests[1].name, "open");
+	assert_eq!(asm.tests[1].cells[0].0, "$nowhere");
+	assert_eq!(
+		asm.warnings,
+		[
+			".in outside of .test block",
+			".test open is missing .end",
+			"Unknown label: nowhere",
+		]
+	);
+}
+
+#[test]
+fn format_canonical_mnemonics() {
+	let src = "mov\n1\n  2\njmp\n# to the end\n$end\n\"jmp\n:end\nexit\n";
+	assert_eq!(
+		gas::format(src, gas::Spelling::Canonical),
+		"copy\n1\n  2\njump\n# to the end\n\t$end\n\"jmp\n:end\nquit\n"
+	);
+	assert_eq!(
+		gas::format(src, gas::Spelling::Short),
+		"mov\n1\n  2\njmp\n# to the end\n\t$end\n\"jmp\n:end\nquit\n"
+	);
+}
+
+#[test]
+fn indented_literals_are_kept() {
+	let src = "write\n  hi\n\tread\n\t$x\n:x\n\t# not a comment\n";
+	let asm = gas::assemble(src);
+	assert_eq!(
+		asm.cells,
+		[
+			Value::I(4),
+			Value::from("  hi"),
+			Value::I(5),
+			Value::I(4),
+			Value::from("\t# not a comment"),
+		]
+	);
+	assert_eq!(
+		gas::format(src, gas::Spelling::Short),
+		"write\n  hi\nread\n\t$x\n:x\n\t# not a comment\n"
+	);
+}
+
+#[test]
+fn format_keeps_cells() {
+	for ent in fs::read_dir("gas").unwrap() {
+		let path = ent.unwrap().path();
+		let src = fs::read_to_string(&path).unwrap();
+		let formatted = gas::format(&src, gas::Spelling::Short);
+		assert_eq!(
+			gas::assemble(&src).cells,
+			gas::assemble(&formatted).cells,
+			"{}",
+			path.display()
+		);
+		assert_eq!(gas::format(&formatted, gas::Spelling::Short), formatted);
+	}
+}
+
+#[test]
+fn sparse_disassembly() {
+	let far = 1 << 40;
+	let mut tape = FxHashMap::default();
+	tape.insert(Value::I(0), Value::I(6));
+	tape.insert(Value::I(1), Value::I(far));
+	tape.insert(Value::I(far), Value::I(3));
+	let symbols = [
+		(String::from("far"), far),
+		(String::from("b"), 0),
+		(String::from("a"), 0),
+	];
+	let (lines, warnings) = gas::disassemble(&tape, &symbols);
+	assert!(warnings.is_empty());
+	assert_eq!(
+		lines,
+		[
+			":a",
+			":b",
+			"jump",
+			"1099511627776",
+			".org 1099511627776",
+			":far",
+			"quit"
+		]
+	);
+	let asm = gas::assemble(&lines.join("\n"));
+	assert!(asm.warnings.is_empty());
+	assert!(!asm.dense());
+	assert_eq!(asm.tape(), tape);
+	assert_eq!(
+		asm.labels,
+		[
+			(String::from("a"), 0),
+			(String::from("b"), 0),
+			(String::from("far"), far),
+		]
+	);
+	let asm = gas::assemble(".org 2\n3\n.org 1\n3\n");
+	assert_eq!(asm.addrs, [2, 3]);
+	assert_eq!(asm.warnings.len(), 1);
+}
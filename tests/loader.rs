@@ -0,0 +1,111 @@
+mod common;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use oil_lang::error::Error;
+use oil_lang::loader::{Loader, Module, Shadow};
+use oil_lang::value::Value;
+
+fn found(loader: &Loader, root: &Path, name: &str) -> Option<PathBuf> {
+	match loader.resolve(Some(root), &Value::from(name)) {
+		Ok(Some(Module::File(path))) => Some(path),
+		Ok(Some(Module::Stdlib(_))) => Some(PathBuf::from("stdlib")),
+		_ => None,
+	}
+}
+
+#[test]
+fn search_order() {
+	let dir = common::temp_dir("search");
+	let (root, inc, envdir) = (dir.join("root"), dir.join("inc"), dir.join("env"));
+	for d in [&root, &inc, &envdir].iter() {
+		fs::create_dir_all(d).unwrap();
+	}
+	fs::write(envdir.join("m"), "3\n").unwrap();
+	let mut loader = Loader::new();
+	loader.search.push(inc.clone());
+	env::set_var("OIL_PATH", &envdir);
+	loader.add_env_path();
+	assert_eq!(found(&loader, &root, "m"), Some(envdir.join("m")));
+	fs::write(inc.join("m"), "3\n").unwrap();
+	assert_eq!(found(&loader, &root, "m"), Some(inc.join("m")));
+	fs::write(root.join("m"), "3\n").unwrap();
+	assert_eq!(found(&loader, &root, "m"), Some(root.join("m")));
+	assert_eq!(found(&loader, &root, "n"), None);
+	fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn shadowing() {
+	let dir = common::temp_dir("shadow");
+	fs::write(dir.join("lib"), "3\n").unwrap();
+	let mut loader = Loader::new();
+	loader
+		.stdlib
+		.insert(String::from("lib"), Rc::new(Default::default()));
+	assert_eq!(found(&loader, &dir, "lib"), Some(dir.join("lib")));
+	loader.shadow = Shadow::Stdlib;
+	assert_eq!(found(&loader, &dir, "lib"), Some(PathBuf::from("stdlib")));
+	fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn sandbox_errors() {
+	let dir = common::temp_dir("sandbox");
+	let (root, outside) = (dir.join("root"), dir.join("outside"));
+	fs::create_dir_all(&root).unwrap();
+	fs::create_dir_all(&outside).unwrap();
+	fs::write(outside.join("evil"), "3\n").unwrap();
+	fs::write(root.join("ok"), "3\n").unwrap();
+	let mut loader = Loader::new();
+	loader.confine(vec![root.clone()]);
+	let resolve = |loader: &Loader, name: &str| loader.resolve(Some(&root), &Value::from(name));
+	assert!(matches!(resolve(&loader, "ok"), Ok(Some(Module::File(_)))));
+	assert!(matches!(
+		resolve(&loader, "../outside/evil"),
+		Err(Error::PathTraversal(_))
+	));
+	let abs = outside.join("evil");
+	assert!(matches!(
+		resolve(&loader, abs.to_str().unwrap()),
+		Err(Error::AbsolutePath(_))
+	));
+	#[cfg(unix)]
+	{
+		std::os::unix::fs::symlink(outside.join("evil"), root.join("link")).unwrap();
+		assert!(matches!(
+			resolve(&loader, "link"),
+			Err(Error::OutsideSandbox(_))
+		));
+	}
+	loader.file_modules = false;
+	assert!(matches!(
+		resolve(&loader, "ok"),
+		Err(Error::FileModulesDisabled(_))
+	));
+	fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn lib_dir_overrides() {
+	let dir = common::temp_dir("libdir");
+	fs::write(dir.join("echo"), "3\n").unwrap();
+	fs::write(dir.join("extra"), "3\n").unwrap();
+	fs::write(dir.join("Not_a_module"), "3\n").unwrap();
+	let mut loader = Loader::new();
+	let embedded = loader.stdlib.contains_key("echo");
+	let shadowed = loader.add_lib_dir(&dir).unwrap();
+	if embedded {
+		assert_eq!(shadowed, ["echo"]);
+	} else {
+		assert!(shadowed.is_empty());
+	}
+	assert_eq!(loader.stdlib["echo"].get(&Value::I(0)), Some(&Value::I(3)));
+	assert!(loader.stdlib.contains_key("extra"));
+	assert!(!loader.stdlib.contains_key("Not_a_module"));
+	assert_eq!(loader.add_lib_dir(&dir).unwrap(), shadowed);
+	fs::remove_dir_all(&dir).ok();
+}
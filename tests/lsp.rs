@@ -0,0 +1,86 @@
+mod common;
+
+use oil_lang::json::Json;
+use oil_lang::loader::Loader;
+use oil_lang::lsp;
+use std::rc::Rc;
+
+const DOC: &str = "read\\n$x\\n:x\\nwirte\\n$y\\ncall\\n# module\\n\\n:x\\n";
+
+fn session(messages: &[String]) -> Vec<Json> {
+	let mut output = Vec::new();
+	let mut loader = Loader::new();
+	loader
+		.stdlib
+		.insert(String::from("mylib"), Rc::new(Default::default()));
+	lsp::serve(&mut common::frame(messages), &mut output, &loader).unwrap();
+	common::unframe(output)
+}
+
+fn request(id: i64, method: &str, line: i64, character: i64) -> String {
+	format!(
+		r#"{{"jsonrpc":"2.0","id":{},"method":"{}","params":{{"textDocument":{{"uri":"file:///a.gas"}},"position":{{"line":{},"character":{}}},"context":{{"includeDeclaration":true}}}}}}"#,
+		id, method, line, character
+	)
+}
+
+#[test]
+fn diagnostics_navigation_and_completion() {
+	let msgs = session(&[
+		String::from(r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#),
+		format!(
+			r#"{{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{{"textDocument":{{"uri":"file:///a.gas","text":"{}"}}}}}}"#,
+			DOC
+		),
+		request(2, "textDocument/definition", 1, 1),
+		request(3, "textDocument/references", 2, 1),
+		request(4, "textDocument/completion", 7, 0),
+		request(5, "textDocument/hover", 0, 0),
+		request(6, "textDocument/completion", 4, 1),
+		String::from(r#"{"jsonrpc":"2.0","id":7,"method":"shutdown"}"#),
+		String::from(r#"{"jsonrpc":"2.0","method":"exit"}"#),
+	]);
+	let result = |id: i64| {
+		msgs.iter()
+			.find(|m| m.get("id").as_i64() == Some(id))
+			.unwrap()
+			.get("result")
+	};
+	assert_eq!(
+		result(1)
+			.get("capabilities")
+			.get("definitionProvider")
+			.as_bool(),
+		Some(true)
+	);
+	let diagnostics = msgs[1].get("params").get("diagnostics").as_array();
+	let messages = diagnostics
+		.iter()
+		.map(|d| d.get("message").as_str().unwrap())
+		.collect::<Vec<_>>();
+	assert_eq!(
+		messages,
+		[
+			"duplicate label: x, first defined on line 3",
+			"unknown label: y",
+			"unknown mnemonic: wirte, quote data with \"",
+		]
+	);
+	assert_eq!(
+		result(2).get("range").get("start").get("line").as_i64(),
+		Some(2)
+	);
+	assert_eq!(result(3).as_array().len(), 3);
+	let modules = result(4).get("items").as_array();
+	assert!(modules
+		.iter()
+		.any(|item| item.get("label").as_str() == Some("mylib")));
+	assert_eq!(
+		result(5).get("contents").get("value").as_str(),
+		Some("opcode 5: read, 1 operand")
+	);
+	let labels = result(6).get("items").as_array();
+	assert_eq!(labels.len(), 1);
+	assert_eq!(labels[0].get("label").as_str(), Some("$x"));
+	assert_eq!(result(7), &Json::Null);
+}
@@ -0,0 +1,77 @@
+mod common;
+
+use fxhash::FxHashMap;
+use std::fs;
+use std::rc::Rc;
+
+use oil_lang::error::Error;
+use oil_lang::gas;
+use oil_lang::loader::{self, Loader};
+use oil_lang::packed::Packed;
+use oil_lang::policy::Policy;
+use oil_lang::value::Value;
+
+#[test]
+fn write_and_parse() {
+	let mut cells = FxHashMap::default();
+	cells.insert(Value::I(0), Value::I(-3));
+	cells.insert(Value::I(1), Value::C('✓'));
+	cells.insert(Value::I(2), Value::S(Rc::new(String::from("a\nb"))));
+	cells.insert(Value::I(3), Value::S(Rc::new(String::from("5"))));
+	cells.insert(Value::I(9), Value::from("123456789012345678901234567890"));
+	cells.insert(Value::I(-7), Value::I(i64::MIN));
+	cells.insert(Value::from("key"), Value::C('x'));
+	let mut image = Packed::new(cells.clone());
+	image.symbols.push((String::from("end"), 9));
+	let mut data = Vec::new();
+	image.write(&mut data).unwrap();
+	let again = Packed::parse(&data).unwrap();
+	assert_eq!(again.cells, cells);
+	assert_eq!(again.symbols, image.symbols);
+	assert!(Packed::parse(&data[..data.len() - 1]).is_err());
+	assert!(Packed::parse(b"\x7foil\x02").is_err());
+	assert!(Packed::parse(b"5\n").is_err());
+}
+
+#[test]
+fn call_loads_image() {
+	let dir = common::temp_dir("packed");
+	let asm = gas::assemble("read\n$x\noutput\n$x\nquit\n:x\n");
+	let mut data = Vec::new();
+	Packed::new(asm.tape()).write(&mut data).unwrap();
+	fs::write(dir.join("echo2"), data).unwrap();
+	assert_eq!(loader::load(&dir.join("echo2")).unwrap(), asm.tape());
+	let mut tape = common::tape(&["14", "echo2", "6", "5", "3", "hi"]);
+	tape.root = Some(dir.clone());
+	let status = tape.run(&mut Loader::new(), &mut Policy::default());
+	fs::remove_dir_all(&dir).ok();
+	assert!(matches!(status, Ok(0)));
+	assert_eq!(tape.tape[&Value::I(6)], Value::from("hi"));
+}
+
+#[test]
+fn bad_images_fail_the_call() {
+	let dir = common::temp_dir("bad-image");
+	let mut data = Vec::new();
+	Packed::new(gas::assemble("quit\n").tape())
+		.write(&mut data)
+		.unwrap();
+	fs::write(dir.join("short"), &data[..data.len() - 1]).unwrap();
+	fs::write(dir.join("v2"), b"\x7foil\x02").unwrap();
+	for &(name, message) in &[
+		("short", "image is truncated"),
+		("v2", "unsupported image version 2"),
+	] {
+		let mut tape = common::tape(&["14", name, "6", "5", "3"]);
+		tape.root = Some(dir.clone());
+		match tape.run(&mut Loader::new(), &mut Policy::default()) {
+			Err(e @ Error::BadModule(..)) => {
+				let e = e.to_string();
+				assert!(e.contains(&dir.join(name).display().to_string()), "{}", e);
+				assert!(e.contains(message), "{}", e);
+			}
+			_ => panic!("expected call {} to fail", name),
+		}
+	}
+	fs::remove_dir_all(&dir).ok();
+}
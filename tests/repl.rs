@@ -0,0 +1,55 @@
+use std::rc::Rc;
+
+use oil_lang::gas;
+use oil_lang::loader::Loader;
+use oil_lang::policy::{Input, Output, Policy};
+use oil_lang::repl::Repl;
+use oil_lang::value::Value;
+
+#[test]
+fn repl_labels_persist() {
+	let mut loader = Loader::new();
+	let mut policy = Policy {
+		input: Input::Buffer(vec![String::from("41")].into()),
+		output: Output::Capture(Vec::new()),
+		..Policy::default()
+	};
+	let mut repl = Repl::new();
+	for line in &[":x read", "increment $x", "write $x"] {
+		if let Err(e) = repl.append(line, &mut loader, &mut policy) {
+			panic!("{}: {}", line, e);
+		}
+	}
+	assert!(repl
+		.append("write $nope", &mut loader, &mut policy)
+		.is_err());
+	assert_eq!(repl.labels.get("x"), Some(&0));
+	match policy.output {
+		Output::Capture(ref out) => assert_eq!(out, b"42"),
+		_ => unreachable!(),
+	}
+	repl.command(".set $x 7", &mut loader, &mut policy).unwrap();
+	assert_eq!(repl.tape.read_val(&Value::I(0)), Value::I(7));
+	let shown = repl.command(".show 0", &mut loader, &mut policy).unwrap();
+	assert_eq!(shown, "0: 7\nidx: 5 dir: true");
+}
+
+#[test]
+fn repl_literals_match_gas() {
+	let mut loader = Loader::new();
+	let mut policy = Policy::default();
+	let mut repl = Repl::new();
+	for line in &["quit \"5\" 'x'", "#gas typed", ":x quit \"5\" 'x' $x"] {
+		if let Err(e) = repl.append(line, &mut loader, &mut policy) {
+			panic!("{}: {}", line, e);
+		}
+	}
+	assert!(repl.typed);
+	let cells = (0..7)
+		.map(|a| repl.tape.read_val(&Value::I(a)))
+		.collect::<Vec<_>>();
+	let asm = gas::assemble("quit\n\"5\"\n'x'\n#gas typed\n:x\nquit\n\"5\"\n'x'\n$x\n");
+	assert_eq!(cells, asm.cells);
+	assert_eq!(cells[1], Value::from("5\""));
+	assert_eq!(cells[4], Value::S(Rc::new(String::from("5"))));
+}
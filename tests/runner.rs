@@ -0,0 +1,74 @@
+mod common;
+
+use std::fs;
+
+use oil_lang::loader::Loader;
+use oil_lang::policy::{Output, Policy};
+use oil_lang::runner::{self, Outcome};
+
+#[test]
+fn discover_programs() {
+	let dir = common::temp_dir("discover");
+	fs::create_dir_all(dir.join("sub")).unwrap();
+	for name in &["b.oil", "a.gas", "sub/c.oil", "notes.txt", "a.out"] {
+		fs::write(dir.join(name), "3\n").unwrap();
+	}
+	let found = runner::discover(&dir).unwrap();
+	assert_eq!(
+		found,
+		[dir.join("a.gas"), dir.join("b.oil"), dir.join("sub/c.oil")]
+	);
+	let single = dir.join("notes.txt");
+	assert_eq!(runner::discover(&single).unwrap(), [single]);
+	fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn run_files_and_blocks() {
+	let dir = common::temp_dir("run-file");
+	let src = "read\n$x\noutput\n$x\nquit\n:x\n\
+		.test echo\n.in hi\n.out hi\n.expect_cell $x hi\n.end\n\
+		.test wrong\n.in a\n.out b\n.expect_cell 5 \"c\"\n.end\n";
+	fs::write(dir.join("echo.gas"), src).unwrap();
+	fs::write(dir.join("echo.in"), "file\n").unwrap();
+	fs::write(dir.join("echo.out"), "file").unwrap();
+	fs::write(dir.join("skip.oil"), "3\n").unwrap();
+	let mut loader = Loader::new();
+	let policy = Policy::default();
+	let cases = runner::run_file(&dir.join("echo.gas"), &mut loader, &policy);
+	let names = cases.iter().map(|c| &c.name[..]).collect::<Vec<_>>();
+	let path = dir.join("echo.gas").display().to_string();
+	assert_eq!(
+		names,
+		[
+			path.clone(),
+			format!("{}::echo", path),
+			format!("{}::wrong", path)
+		]
+	);
+	assert!(matches!(cases[0].outcome, Outcome::Pass));
+	assert!(matches!(cases[1].outcome, Outcome::Pass));
+	match cases[2].outcome {
+		Outcome::Fail(ref lines) => assert_eq!(
+			lines,
+			&["line 1:", "-b", "+a", "cell 5: expected \"c\", got a"]
+		),
+		_ => panic!("wrong block passed"),
+	}
+	let cases = runner::run_file(&dir.join("skip.oil"), &mut loader, &policy);
+	assert!(matches!(cases[0].outcome, Outcome::Skip));
+	let policy = Policy {
+		output: Output::Deny,
+		..Policy::default()
+	};
+	let cases = runner::run_file(&dir.join("echo.gas"), &mut loader, &policy);
+	assert!(matches!(cases[0].outcome, Outcome::Error(ref e) if e.contains("denied")));
+	fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn diff_lines() {
+	assert_eq!(runner::diff("a\nb\n", "a\nc\n"), ["line 2:", "-b", "+c"]);
+	assert_eq!(runner::diff("a\n", "a\nb\n"), ["line 2:", "+b"]);
+	assert_eq!(runner::diff("a\n", "a"), ["trailing newline differs"]);
+}
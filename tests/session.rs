@@ -0,0 +1,44 @@
+mod common;
+
+use std::io::Cursor;
+
+use oil_lang::loader::Loader;
+use oil_lang::policy::{Input, Output, Policy};
+use oil_lang::session::Session;
+use oil_lang::value::Value;
+
+fn run(policy: &mut Policy) -> Option<Session> {
+	let mut tape = common::tape(&["5", "20", "15", "21", "4", "20", "4", "21"]);
+	tape.tape.insert(Value::I(21), Value::I(1_000_000));
+	tape.run(&mut Loader::new(), policy).ok()?;
+	policy.record.take()
+}
+
+#[test]
+fn record_and_replay() {
+	let mut policy = Policy {
+		input: Input::Buffer(vec![String::from("007")].into()),
+		output: Output::Capture(Vec::new()),
+		record: Some(Session::new(42)),
+		..Policy::default()
+	};
+	policy.seed(42);
+	let recorded = run(&mut policy).unwrap();
+	assert_eq!(recorded.input, b"007\n");
+	assert!(recorded.output.starts_with(b"007"));
+	let mut log = Vec::new();
+	recorded.write(&mut log).unwrap();
+	let session = Session::parse(&log).unwrap();
+	assert_eq!(session.seed, 42);
+	assert_eq!(session.input, recorded.input);
+	assert_eq!(session.output, recorded.output);
+	let mut policy = Policy {
+		input: Input::Reader(Box::new(Cursor::new(session.input))),
+		output: Output::Capture(Vec::new()),
+		record: Some(Session::new(session.seed)),
+		..Policy::default()
+	};
+	policy.seed(session.seed);
+	assert_eq!(run(&mut policy).unwrap().output, session.output);
+	assert!(Session::parse(&log[..log.len() - 3]).is_err());
+}
@@ -0,0 +1,225 @@
+use oil_lang::loader::Loader;
+use oil_lang::policy::Policy;
+use oil_lang::tape::Tape;
+use oil_lang::value::Value;
+
+const OUT: i64 = 1000;
+const IN: i64 = 2000;
+
+const CASES: &[(&str, &[&str], &[&str])] = &[
+	("abs", &["-5"], &["5"]),
+	("abs", &["7"], &["7"]),
+	("abs", &["0"], &["0"]),
+	("abs", &["-9223372036854775808"], &["9223372036854775808"]),
+	(
+		"abs",
+		&["-123456789012345678901234"],
+		&["123456789012345678901234"],
+	),
+	("add", &["2", "3"], &["5"]),
+	("add", &["-4", "1"], &["-3"]),
+	("add", &["5", "-7"], &["-2"]),
+	("add", &["0", "0"], &["0"]),
+	(
+		"add",
+		&["9223372036854775807", "1"],
+		&["9223372036854775808"],
+	),
+	(
+		"add",
+		&["-9223372036854775808", "-1"],
+		&["-9223372036854775809"],
+	),
+	("call", &["echo", "x"], &["x"]),
+	("call", &["strlen", "abc"], &["3"]),
+	("commainstr", &["a,b"], &["1"]),
+	("commainstr", &["ab"], &["0"]),
+	("commainstr", &[""], &["0"]),
+	("commainstr", &["ä,ö"], &["1"]),
+	("div", &["7", "2"], &["3"]),
+	("div", &["9", "3"], &["3"]),
+	("div", &["2", "5"], &["0"]),
+	("division", &["7", "2"], &["3", "1"]),
+	("division", &["9", "3"], &["3", "0"]),
+	("echo", &["hello"], &["hello"]),
+	("echo", &[""], &[""]),
+	("echo", &["-5"], &["-5"]),
+	("echo", &["héllo wörld ✓"], &["héllo wörld ✓"]),
+	(
+		"echo",
+		&["123456789012345678901234567890"],
+		&["123456789012345678901234567890"],
+	),
+	("email", &["a@b.c"], &["1"]),
+	("email", &["ü@ö.de"], &["1"]),
+	("email", &["ab.c"], &["0"]),
+	("email", &["a@b"], &["0"]),
+	("email", &[""], &["0"]),
+	("fibonacci", &["0"], &["0"]),
+	("fibonacci", &["1"], &["1"]),
+	("fibonacci", &["10"], &["55"]),
+	("fibonacci", &["20"], &["6765"]),
+	("head", &["hello"], &["h"]),
+	("head", &["ünïcode"], &["ü"]),
+	("head", &["42"], &["4"]),
+	("headtail", &["hello"], &["h", "ello"]),
+	("headtail", &["ab"], &["a", "b"]),
+	("headtail", &["日本語"], &["日", "本語"]),
+	("hello_world", &[], &["Hello, World!"]),
+	("invert", &["0"], &["1"]),
+	("invert", &["1"], &["0"]),
+	("invert", &["5"], &["0"]),
+	("iseq", &["3", "3"], &["1"]),
+	("iseq", &["3", "4"], &["0"]),
+	("iseq", &["-3", "3"], &["0"]),
+	("iseq", &["abc", "abc"], &["1"]),
+	("iseq", &["", ""], &["1"]),
+	("iseq", &["é", "e"], &["0"]),
+	(
+		"iseq",
+		&["12345678901234567890", "12345678901234567890"],
+		&["1"],
+	),
+	("isnegative", &["-1"], &["1"]),
+	("isnegative", &["0"], &["0"]),
+	("isnegative", &["5"], &["0"]),
+	("isnegative", &["-99999999999999999999"], &["1"]),
+	("isnegative", &["99999999999999999999"], &["0"]),
+	("join", &["foo", "bar"], &["foobar"]),
+	("join", &["", "x"], &["x"]),
+	("join", &["日", "本"], &["日本"]),
+	("join", &["-1", "23"], &["-123"]),
+	("leq", &["1", "2"], &["1"]),
+	("leq", &["2", "2"], &["1"]),
+	("leq", &["3", "2"], &["0"]),
+	("leq", &["-5", "2"], &["1"]),
+	("leq", &["2", "-5"], &["0"]),
+	("mul", &["6", "7"], &["42"]),
+	("mul", &["-3", "4"], &["-12"]),
+	("mul", &["3", "-4"], &["-12"]),
+	("mul", &["-3", "-4"], &["12"]),
+	("mul", &["0", "5"], &["0"]),
+	("sleep", &["0"], &[]),
+	("sleep", &["3"], &[]),
+	("splitonce", &["a,b,c", ","], &["a", "b,c"]),
+	("splitonce", &["abc", ","], &["abc", ""]),
+	("splitonce", &["ä-ö", "-"], &["ä", "ö"]),
+	("startswith", &["hello", "he"], &["1"]),
+	("startswith", &["hello", "lo"], &["0"]),
+	("startswith", &["hello", ""], &["1"]),
+	("startswith", &["he", "hello"], &["0"]),
+	("startswith", &["日本語", "日"], &["1"]),
+	("strinstr", &["ll", "hello"], &["1"]),
+	("strinstr", &["xy", "hello"], &["0"]),
+	("strinstr", &["", "hello"], &["1"]),
+	("strinstr", &["本", "日本語"], &["1"]),
+	("strlen", &["hello"], &["5"]),
+	("strlen", &[""], &["0"]),
+	("strlen", &["日本語"], &["3"]),
+	("strlen", &["-42"], &["3"]),
+	("strlen", &["12345678901234567890"], &["20"]),
+	("strsplit", &["a,b,c", ","], &["3", "a", "b", "c"]),
+	("strsplit", &["abc", ","], &["1", "abc"]),
+	("strsplit", &["", ","], &["1", ""]),
+	("strsplit", &["é;ü", ";"], &["2", "é", "ü"]),
+	("sub", &["5", "3"], &["2"]),
+	("sub", &["3", "5"], &["-2"]),
+	("sub", &["-2", "-2"], &["0"]),
+	(
+		"sub",
+		&["-9223372036854775808", "1"],
+		&["-9223372036854775809"],
+	),
+	(
+		"sub",
+		&["9223372036854775808", "1"],
+		&["9223372036854775807"],
+	),
+	("swap", &["1", "2"], &["2", "1"]),
+	("swap", &["a", ""], &["", "a"]),
+	("trimend", &["hi  "], &["hi"]),
+	("trimend", &["  hi"], &["  hi"]),
+	("trimend", &["   "], &[""]),
+	("trimend", &[""], &[""]),
+	("trimstart", &["  hi"], &["hi"]),
+	("trimstart", &["hi  "], &["hi  "]),
+	("trimstart", &["  ä"], &["ä"]),
+	("truediv", &["7", "2"], &["3.5"]),
+	("truediv", &["6", "3"], &["2"]),
+	("truediv", &["1", "4"], &["0.25"]),
+	("uniquechars", &["hello"], &["helo"]),
+	("uniquechars", &["aaa"], &["a"]),
+	("uniquechars", &[""], &[""]),
+	("uniquechars", &["ééa"], &["éa"]),
+];
+
+fn call(loader: &mut Loader, name: &Value, inputs: &[Value]) -> Vec<Value> {
+	let mut tape = Tape::new(None);
+	tape.tape.insert(Value::I(0), Value::I(14));
+	tape.tape.insert(Value::I(1), name.clone());
+	tape.tape.insert(Value::I(2), Value::I(OUT));
+	tape.tape.insert(Value::I(3), Value::I(IN));
+	for (i, val) in inputs.iter().enumerate() {
+		tape.tape.insert(Value::I(IN + i as i64), val.clone());
+	}
+	let mut policy = Policy::default();
+	if let Err(e) = tape.op14(loader, &mut policy) {
+		panic!("call {}: {}", name, e);
+	}
+	let mut outputs = Vec::new();
+	while let Some(val) = tape.get(&Value::I(OUT + outputs.len() as i64)) {
+		outputs.push(val.clone());
+	}
+	outputs
+}
+
+fn values(cells: &[&str]) -> Vec<Value> {
+	cells.iter().map(|&cell| Value::from(cell)).collect()
+}
+
+#[test]
+fn stdlib_cases() {
+	let mut loader = Loader::new();
+	if loader.stdlib.is_empty() {
+		return;
+	}
+	let mut failures = Vec::new();
+	for &(name, inputs, expected) in CASES {
+		if !loader.stdlib.contains_key(name) {
+			failures.push(format!("{}: not embedded", name));
+			continue;
+		}
+		let actual = call(&mut loader, &Value::from(name), &values(inputs));
+		let expected = values(expected);
+		if actual != expected {
+			failures.push(format!(
+				"{}{:?}: expected {:?}, got {:?}",
+				name, inputs, expected, actual
+			));
+		}
+	}
+	assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
+
+#[test]
+fn stdlib_quine() {
+	let mut loader = Loader::new();
+	if let Some(image) = loader.stdlib.get("quine").cloned() {
+		let source = (0..)
+			.map_while(|i| image.get(&Value::I(i)).cloned())
+			.collect::<Vec<_>>();
+		assert_eq!(call(&mut loader, &Value::from("quine"), &[]), source);
+	}
+}
+
+#[test]
+fn stdlib_covered() {
+	let loader = Loader::new();
+	let mut missing = loader
+		.stdlib
+		.keys()
+		.filter(|&name| name != "quine" && !CASES.iter().any(|case| case.0 == name))
+		.collect::<Vec<_>>();
+	missing.sort();
+	assert!(missing.is_empty(), "modules without cases: {:?}", missing);
+}
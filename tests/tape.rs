@@ -0,0 +1,255 @@
+mod common;
+
+use std::fs;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use oil_lang::error::{opname, Error};
+use oil_lang::loader::Loader;
+use oil_lang::policy::{Buffering, Eof, Input, Output, Policy, Random};
+use oil_lang::tape::{ARGS, ENV};
+use oil_lang::value::Value;
+
+#[test]
+fn exit_status() {
+	let mut loader = Loader::new();
+	let prog = ["8", "4", "3", "", "41"];
+	let mut policy = Policy::default();
+	assert_eq!(
+		common::tape(&prog).run(&mut loader, &mut policy).ok(),
+		Some(0)
+	);
+	policy.exit_cell = Some(Value::I(4));
+	assert_eq!(
+		common::tape(&prog).run(&mut loader, &mut policy).ok(),
+		Some(42)
+	);
+	policy.exit_cell = Some(Value::I(3));
+	assert_eq!(
+		common::tape(&prog).run(&mut loader, &mut policy).ok(),
+		Some(1)
+	);
+	policy.exit_cell = Some(Value::I(-9));
+	assert_eq!(
+		common::tape(&prog).run(&mut loader, &mut policy).ok(),
+		Some(0)
+	);
+}
+
+#[test]
+fn args_and_env() {
+	let mut t = common::tape(&[]);
+	t.set_list(ARGS, &[String::from("a"), String::from("12")]);
+	t.set_list(ENV, &[String::from("HOME=/home/oil")]);
+	assert_eq!(t.read_val(&Value::I(ARGS)), Value::I(2));
+	assert_eq!(t.read_val(&Value::I(ARGS - 1)), Value::from("a"));
+	assert_eq!(t.read_val(&Value::I(ARGS - 2)), Value::I(12));
+	assert_eq!(t.read_val(&Value::I(ENV)), Value::I(1));
+	assert_eq!(
+		t.read_val(&Value::I(ENV - 1)),
+		Value::from("HOME=/home/oil")
+	);
+	t.set_list(ARGS - 10, &[]);
+	assert_eq!(t.get(&Value::I(ARGS - 10)), None);
+}
+
+#[test]
+fn eof_policy() {
+	let mut loader = Loader::new();
+	let prog = ["5", "10", "6", "0"];
+	let run = |eof| {
+		let mut policy = Policy {
+			input: Input::Buffer(vec![String::from("x")].into()),
+			eof,
+			step_limit: Some(100),
+			..Policy::default()
+		};
+		let mut t = common::tape(&prog);
+		let result = t.run(&mut Loader::new(), &mut policy).is_ok();
+		(result, t.read_val(&Value::I(10)), t.read_val(&Value::I(11)))
+	};
+	assert!(!run(Eof::Empty).0);
+	assert_eq!(run(Eof::Quit), (true, Value::from("x"), Value::I(0)));
+	let (_, val, _) = run(Eof::Sentinel(Value::I(-1)));
+	assert_eq!(val, Value::I(-1));
+	let mut policy = Policy {
+		input: Input::Buffer(Default::default()),
+		eof: Eof::Flag(Value::I(11)),
+		..Policy::default()
+	};
+	let mut t = common::tape(&["5", "10"]);
+	assert!(t.run(&mut loader, &mut policy).is_ok());
+	assert_eq!(t.read_val(&Value::I(10)), Value::from(""));
+	assert_eq!(t.read_val(&Value::I(11)), Value::I(1));
+}
+
+#[test]
+fn module_reads_ignore_eof_policy() {
+	let dir = common::temp_dir("module-eof");
+	fs::write(dir.join("rd"), "5\n9\n5\n10\n4\n10\n3\n").unwrap();
+	let prog = ["14", "rd", "100", "200", "8", "100", "3"];
+	for eof in [
+		Eof::Empty,
+		Eof::Sentinel(Value::I(-7)),
+		Eof::Flag(Value::I(3)),
+		Eof::Quit,
+	] {
+		let mut policy = Policy {
+			eof,
+			..Policy::default()
+		};
+		let mut t = common::tape(&prog);
+		t.root = Some(dir.clone());
+		t.tape.insert(Value::I(200), Value::from("a"));
+		assert!(matches!(t.run(&mut Loader::new(), &mut policy), Ok(0)));
+		assert_eq!(t.read_val(&Value::I(100)), Value::I(1));
+		assert_eq!(t.read_val(&Value::I(3)), Value::I(200));
+	}
+	fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn byte_mode() {
+	let mut loader = Loader::new();
+	let mut policy = Policy {
+		input: Input::Buffer(vec![String::from("hé")].into()),
+		output: Output::Capture(Vec::new()),
+		eof: Eof::Quit,
+		bytes: true,
+		..Policy::default()
+	};
+	let mut t = common::tape(&["5", "10", "4", "10", "6", "0"]);
+	assert!(t.run(&mut loader, &mut policy).is_ok());
+	assert_eq!(t.read_val(&Value::I(10)), Value::I(10));
+	match policy.output {
+		Output::Capture(ref out) => assert_eq!(out, "hé\n".as_bytes()),
+		_ => unreachable!(),
+	}
+}
+
+struct Closed;
+
+impl Write for Closed {
+	fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+		Err(io::ErrorKind::BrokenPipe.into())
+	}
+	fn flush(&mut self) -> io::Result<()> {
+		Ok(())
+	}
+}
+
+#[test]
+fn write_errors_stop_the_program() {
+	let mut loader = Loader::new();
+	for &buffering in &[Buffering::Block, Buffering::Line, Buffering::Unbuffered] {
+		let mut policy = Policy {
+			output: Output::Writer(Box::new(Closed)),
+			buffering,
+			step_limit: Some(100),
+			..Policy::default()
+		};
+		match common::tape(&["4", "0", "6", "0"]).run(&mut loader, &mut policy) {
+			Err(Error::Write(ref e)) => assert_eq!(e.kind(), io::ErrorKind::BrokenPipe),
+			_ => panic!("expected a write error"),
+		}
+	}
+}
+
+#[test]
+fn denied_operations() {
+	let mut loader = Loader::new();
+	type Deny = fn(&mut Policy);
+	let cases: &[(&[&str], Deny, i64)] = &[
+		(&["5", "10"], |p| p.input = Input::Deny, 5),
+		(&["4", "10"], |p| p.output = Output::Deny, 4),
+		(&["11"], |p| p.output = Output::Deny, 11),
+		(&["15", "10"], |p| p.rand = Random::Deny, 15),
+	];
+	for &(prog, deny, op) in cases {
+		let mut policy = Policy::default();
+		deny(&mut policy);
+		match common::tape(prog).run(&mut loader, &mut policy) {
+			Err(e @ Error::Denied(..)) => {
+				assert!(matches!(e, Error::Denied(o, Value::I(0)) if o == op));
+				assert!(e
+					.to_string()
+					.contains(&format!("(opcode {}) denied at 0", op)));
+			}
+			_ => panic!("opcode {} was not denied", op),
+		}
+	}
+}
+
+#[test]
+fn call_allow_list() {
+	let mut loader = Loader::new();
+	let prog = ["14", "missing", "20", "30", "3"];
+	let mut policy = Policy::default();
+	policy.allow_module(String::from("other"));
+	match common::tape(&prog).run(&mut loader, &mut policy) {
+		Err(e @ Error::ModuleDenied(..)) => {
+			assert!(matches!(e, Error::ModuleDenied(ref name, Value::I(0)) if name == "missing"));
+			assert!(e.to_string().contains("module missing is not allowed"));
+		}
+		_ => panic!("call was not denied"),
+	}
+	policy.allow_module(String::from("missing"));
+	assert!(matches!(
+		common::tape(&prog).run(&mut loader, &mut policy),
+		Ok(0)
+	));
+}
+
+#[test]
+fn output_limit() {
+	let mut loader = Loader::new();
+	for &(prog, op) in &[
+		(&["4", "3", "3", "hello"][..], 4),
+		(&["11", "11", "3"][..], 11),
+	] {
+		let mut policy = Policy {
+			output: Output::Capture(Vec::new()),
+			output_limit: Some(1),
+			..Policy::default()
+		};
+		match common::tape(prog).run(&mut loader, &mut policy) {
+			Err(e @ Error::OutputLimit(..)) => {
+				assert!(matches!(e, Error::OutputLimit(1, o, _) if o == op));
+				assert!(e.to_string().starts_with(&format!(
+					"{} (opcode {}) exceeded",
+					opname(op),
+					op
+				)));
+			}
+			_ => panic!("opcode {} was not limited", op),
+		}
+	}
+}
+
+#[test]
+fn module_images_are_shared() {
+	let dir = common::temp_dir("cow");
+	let path = dir.join("inc");
+	fs::write(&path, "8\n6\n4\n6\n3\n\n41\n").unwrap();
+	let mut loader = Loader::new();
+	let mut policy = Policy::default();
+	let prog = ["14", "inc", "100", "200", "14", "inc", "101", "200", "3"];
+	let mut t = common::tape(&prog);
+	t.root = Some(dir.clone());
+	assert!(matches!(t.run(&mut loader, &mut policy), Ok(0)));
+	assert_eq!(t.read_val(&Value::I(100)), Value::I(42));
+	assert_eq!(t.read_val(&Value::I(101)), Value::I(42));
+	let image = loader.image(&path).unwrap_or_else(|e| panic!("{}", e));
+	assert_eq!(image[&Value::I(6)], Value::I(41));
+	assert_eq!(loader.modcache.len(), 1);
+	fs::write(&path, "3\n").unwrap();
+	let mut t = common::tape(&prog);
+	t.root = Some(dir.clone());
+	assert!(matches!(t.run(&mut loader, &mut policy), Ok(0)));
+	assert_eq!(t.read_val(&Value::I(101)), Value::I(42));
+	assert!(Rc::ptr_eq(
+		&image,
+		&loader.image(&path).unwrap_or_else(|e| panic!("{}", e))
+	));
+	fs::remove_dir_all(&dir).ok();
+}